@@ -0,0 +1,128 @@
+use crate::{CSVProcessor, SOURCE_LINE, drift};
+use crate::{Error, Result};
+use sqlx::PgConnection;
+
+const STAGING_TABLE: &str = "append_staging";
+
+/// Postgres folds unquoted identifiers to lower case.
+fn find<'a>(mut cols: impl Iterator<Item = &'a String>, col: &str) -> Option<usize> {
+    cols.position(|other| other.eq_ignore_ascii_case(col))
+}
+
+impl CSVProcessor {
+    /// Adds the csv's columns to the existing table and fills them from the rows whose key
+    /// matches. Pass a transaction, so that a failed append leaves the table as it was. The key
+    /// column must exist in both the csv and the table. Keys without a match are reported.
+    pub async fn append_db(&self, conn: &mut PgConnection, force: bool) -> Result<()> {
+        let existing = drift::existing_columns(conn, &self.table_name).await?;
+        if existing.is_empty() {
+            return Err(Error::NoTable(self.table_name.clone()));
+        }
+        let key = &self.columns[self.key_idx];
+        let table_key = existing
+            .iter()
+            .map(|(col, _)| col)
+            .find(|col| col.eq_ignore_ascii_case(key))
+            .ok_or_else(|| Error::NoJoinColumn {
+                table: self.table_name.clone(),
+                column: key.clone(),
+            })?;
+        let key_idx = self.key_idx;
+        let new_idxs: Vec<_> = (0..self.columns.len()).filter(|i| *i != key_idx).collect();
+        if new_idxs.is_empty() {
+            return Err(Error::NothingToAppend);
+        }
+        println!(
+            "INFO: Appending to {:?}, joining csv column {key:?} on table column {table_key:?}.",
+            self.table_name
+        );
+
+        let conflicts: Vec<_> = new_idxs
+            .iter()
+            .map(|i| &self.columns[*i])
+            .filter(|col| find(existing.iter().map(|(col, _)| col), col).is_some())
+            .collect();
+        if !conflicts.is_empty() && !force {
+            return Err(Error::ColumnsExist {
+                table: self.table_name.clone(),
+                columns: conflicts.into_iter().cloned().collect(),
+            });
+        }
+
+        let mut queries = vec![];
+        for col in conflicts {
+            queries.push(format!(
+                "ALTER TABLE {} DROP COLUMN {}",
+                self.table_name, col
+            ));
+        }
+        for i in new_idxs.iter() {
+            queries.push(format!(
+                "ALTER TABLE {} ADD COLUMN {} {}",
+                self.table_name, self.columns[*i], self.header_types[*i]
+            ));
+        }
+        let staging_cols: Vec<_> = self
+            .columns
+            .iter()
+            .zip(self.header_types.iter())
+            .map(|(col, col_type)| format!("{col} {col_type}"))
+            .chain([format!("{SOURCE_LINE} BIGINT"), "id INTEGER".to_string()])
+            .collect();
+        queries.push(format!(
+            "CREATE TEMP TABLE {STAGING_TABLE}({}) ON COMMIT DROP",
+            staging_cols.join(",")
+        ));
+        for query in queries {
+            println!("INFO: Running SQL query \"{query}\";");
+            sqlx::query(&query).execute(&mut *conn).await?;
+        }
+        self.copy_rows(conn, STAGING_TABLE).await?;
+
+        let set_cols: Vec<_> = new_idxs
+            .iter()
+            .map(|i| format!("{col} = s.{col}", col = self.columns[*i]))
+            .collect();
+        let update_q = format!(
+            "UPDATE {tbl} SET {} FROM {STAGING_TABLE} s WHERE {tbl}.{table_key}::TEXT = s.{key}::TEXT",
+            set_cols.join(", "),
+            tbl = self.table_name,
+        );
+        println!("INFO: Running SQL query \"{update_q}\";");
+        let filled = sqlx::query(&update_q)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+        let num_rows: i64 =
+            sqlx::query_scalar(&format!("SELECT count(*) FROM {}", self.table_name))
+                .fetch_one(&mut *conn)
+                .await?;
+        println!(
+            "INFO: Filled {filled} of {num_rows} rows in {:?}.",
+            self.table_name
+        );
+
+        let unmatched: Vec<String> = sqlx::query_scalar(&format!(
+            r#"
+            SELECT s.{key}::TEXT FROM {STAGING_TABLE} s
+            WHERE s.{key} IS NOT NULL AND NOT EXISTS (
+                SELECT FROM {tbl} WHERE {tbl}.{table_key}::TEXT = s.{key}::TEXT
+            )
+            "#,
+            tbl = self.table_name,
+        ))
+        .fetch_all(&mut *conn)
+        .await?;
+        if !unmatched.is_empty() {
+            eprintln!(
+                "WARNING: {} keys in the csv match no row in {:?}: {:?}",
+                unmatched.len(),
+                self.table_name,
+                unmatched
+            );
+        }
+
+        self.create_indexes(conn, new_idxs).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,55 @@
+//! The audit log in screenmap.audit_log, of who imported, changed, deleted or exported which
+//! screen and when. Entries are only ever added; the table refuses updates and deletes.
+use crate::Result;
+use sqlx::PgExecutor;
+
+/// Who an entry is by.
+#[derive(Debug, Clone)]
+pub struct Actor {
+    /// The screenmap user, None for process_csv.
+    pub user_id: Option<i64>,
+    /// The screenmap username, or the account process_csv ran as.
+    pub name: String,
+    /// "process_csv", "web" for a browser session or "api" for an API token.
+    pub via: String,
+}
+
+impl Actor {
+    /// process_csv, as the account running it.
+    pub fn command_line() -> Self {
+        let name = std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .unwrap_or_else(|_| "unknown".to_string());
+        Actor {
+            user_id: None,
+            name,
+            via: "process_csv".to_string(),
+        }
+    }
+}
+
+/// Adds an entry for `action` on `screen`, or on no screen in particular with None. Pass the
+/// transaction making the change, so the entry is only kept if the change is.
+pub async fn record<'e>(
+    executor: impl PgExecutor<'e>,
+    actor: &Actor,
+    action: &str,
+    screen: Option<&str>,
+    detail: Option<&str>,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO screenmap.audit_log (user_id, actor, via, action, screen, detail)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+    )
+    .bind(actor.user_id)
+    .bind(&actor.name)
+    .bind(&actor.via)
+    .bind(action)
+    .bind(screen)
+    .bind(detail)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,138 @@
+use crate::{CSVProcessor, Error, Result};
+use sha2::{Digest, Sha256};
+use sqlx::{Connection, PgConnection, PgPool};
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// sha256 of the file as stored, so a recompressed file counts as changed.
+pub fn checksum(fpath: &Path) -> Result<String> {
+    let mut file = File::open(fpath).map_err(|source| Error::Read {
+        path: fpath.display().to_string(),
+        source,
+    })?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// What was recorded about the last import into a table.
+#[derive(Debug, sqlx::FromRow)]
+pub struct Recorded {
+    pub checksum: String,
+    pub key_column: Option<String>,
+    /// The project the screen is in, None if it is published.
+    pub project_id: Option<i64>,
+}
+
+/// The last import into `table_name`, if the table still exists.
+pub async fn recorded_import(pool: &PgPool, table_name: &str) -> Result<Option<Recorded>> {
+    Ok(sqlx::query_as(
+        r#"
+        SELECT checksum, key_column, project_id FROM screenmap.screens
+        WHERE name = $1 AND EXISTS (
+            SELECT FROM pg_tables WHERE tablename = $1 AND schemaname = current_schema()
+        )
+        "#,
+    )
+    .bind(table_name)
+    .fetch_optional(pool)
+    .await?)
+}
+
+pub async fn record_import(
+    conn: &mut PgConnection,
+    table_name: &str,
+    source: &str,
+    checksum: &str,
+    key_column: &str,
+    preamble: &[String],
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO screenmap.screens (name, source, checksum, key_column, preamble)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (name) DO UPDATE
+        SET source = EXCLUDED.source, checksum = EXCLUDED.checksum,
+            key_column = EXCLUDED.key_column, preamble = EXCLUDED.preamble, imported_at = now()
+        "#,
+    )
+    .bind(table_name)
+    .bind(source)
+    .bind(checksum)
+    .bind(key_column)
+    .bind(preamble)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// The id of `project`, which imports can put their screen in.
+pub async fn project_id(pool: &PgPool, project: &str) -> Result<i64> {
+    sqlx::query_scalar("SELECT id FROM screenmap.projects WHERE name = $1")
+        .bind(project)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| Error::NoProject(project.to_string()))
+}
+
+/// Moves the recorded screen `table_name` into the project with `project_id`, so that only its
+/// members can see it.
+pub async fn set_project(conn: &mut PgConnection, table_name: &str, project_id: i64) -> Result<()> {
+    sqlx::query("UPDATE screenmap.screens SET project_id = $2 WHERE name = $1")
+        .bind(table_name)
+        .bind(project_id)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Replaces the recorded roles of the columns of `table_name`.
+pub async fn record_columns(
+    conn: &mut PgConnection,
+    table_name: &str,
+    roles: &[(String, &'static str)],
+) -> Result<()> {
+    let mut tx = conn.begin().await?;
+    sqlx::query("DELETE FROM screenmap.screen_columns WHERE screen = $1")
+        .bind(table_name)
+        .execute(&mut *tx)
+        .await?;
+    let (columns, roles): (Vec<_>, Vec<_>) = roles.iter().cloned().unzip();
+    sqlx::query(
+        r#"
+        INSERT INTO screenmap.screen_columns (screen, column_name, role)
+        SELECT $1, * FROM UNNEST($2::TEXT[], $3::TEXT[])
+        "#,
+    )
+    .bind(table_name)
+    .bind(columns)
+    .bind(roles)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+impl CSVProcessor {
+    /// Records an import of `source` that `write_db` has loaded, with its key column and the
+    /// role of each column, in the transaction that loaded it.
+    pub async fn record(
+        &self,
+        conn: &mut PgConnection,
+        source: &str,
+        checksum: &str,
+        preamble: &[String],
+    ) -> Result<()> {
+        record_import(
+            &mut *conn,
+            &self.table_name,
+            source,
+            checksum,
+            &self.columns[self.key_idx].to_ascii_lowercase(),
+            preamble,
+        )
+        .await?;
+        record_columns(conn, &self.table_name, &self.column_roles()).await
+    }
+}
@@ -0,0 +1,76 @@
+use crate::{Error, Result};
+use regex::Regex;
+
+/// Patterns tried in order when no --cys-pattern is passed. Each pattern must capture `residue`
+/// and at least one of `accession` or `gene`.
+pub const DEFAULT_PATTERNS: &[&str] = &[
+    // sp|P04637|P53_HUMAN C176
+    r"^(?:sp|tr)\|(?P<accession>[^|]+)\|\S+[ _]C(?P<residue>\d+)$",
+    // P04637_C176, P04637-2_C176
+    r"^(?P<accession>(?:[OPQ][0-9][A-Z0-9]{3}[0-9]|[A-NR-Z][0-9](?:[A-Z][A-Z0-9]{2}[0-9]){1,2})(?:-\d+)?)_C(?P<residue>\d+)$",
+    // TP53_C176
+    r"^(?P<gene>[A-Za-z0-9][A-Za-z0-9.-]*)_C(?P<residue>\d+)$",
+];
+
+/// Columns added to every screen to hold the parsed cysteine identifier.
+pub const CYS_COLUMNS: &[(&str, &str)] = &[
+    ("cys_accession", "TEXT"),
+    ("cys_gene", "TEXT"),
+    ("cys_residue", "INTEGER"),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CysSite {
+    pub accession: Option<String>,
+    pub gene: Option<String>,
+    pub residue: i32,
+}
+
+pub struct CysIdParser {
+    patterns: Vec<Regex>,
+}
+
+impl CysIdParser {
+    pub fn new(patterns: &[String]) -> Result<Self> {
+        let patterns = if patterns.is_empty() {
+            DEFAULT_PATTERNS.iter().map(|p| p.to_string()).collect()
+        } else {
+            patterns.to_vec()
+        };
+        let patterns = patterns
+            .iter()
+            .map(|p| {
+                let re = Regex::new(p)?;
+                let names: Vec<_> = re.capture_names().flatten().collect();
+                if !names.contains(&"residue") {
+                    return Err(Error::CysPattern {
+                        pattern: p.clone(),
+                        reason: "has no (?P<residue>...) group",
+                    });
+                } else if !names.contains(&"accession") && !names.contains(&"gene") {
+                    return Err(Error::CysPattern {
+                        pattern: p.clone(),
+                        reason: "needs an (?P<accession>...) or (?P<gene>...) group",
+                    });
+                }
+                Ok(re)
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { patterns })
+    }
+
+    /// Parses an identifier with the first pattern that matches it.
+    pub fn parse(&self, id: &str) -> Option<CysSite> {
+        let id = id.trim();
+        self.patterns.iter().find_map(|re| {
+            let caps = re.captures(id)?;
+            let residue = caps.name("residue")?.as_str().parse().ok()?;
+            let group = |name| caps.name(name).map(|m| m.as_str().to_string());
+            Some(CysSite {
+                accession: group("accession"),
+                gene: group("gene"),
+                residue,
+            })
+        })
+    }
+}
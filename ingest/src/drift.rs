@@ -0,0 +1,126 @@
+use crate::Result;
+use crate::cys_id::CYS_COLUMNS;
+use crate::rollup::NUM_PEPTIDES;
+use crate::{RecordType, SOURCE_LINE};
+use sqlx::PgConnection;
+use std::fmt::Display;
+
+#[derive(Debug, PartialEq)]
+pub enum ColumnChange {
+    Added(String, RecordType),
+    Removed(String, RecordType),
+    Widened(String, RecordType, RecordType),
+    Narrowed(String, RecordType, RecordType),
+}
+
+impl ColumnChange {
+    /// Whether views of the existing table may stop working after the change.
+    pub fn is_breaking(&self) -> bool {
+        matches!(self, ColumnChange::Removed(..) | ColumnChange::Narrowed(..))
+    }
+}
+
+impl Display for ColumnChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColumnChange::Added(col, new) => write!(f, "+ {col} {new}"),
+            ColumnChange::Removed(col, old) => write!(f, "- {col} {old}"),
+            ColumnChange::Widened(col, old, new) => write!(f, "~ {col} {old} -> {new} (widened)"),
+            ColumnChange::Narrowed(col, old, new) => {
+                write!(f, "~ {col} {old} -> {new} (narrowed)")
+            }
+        }
+    }
+}
+
+/// Postgres types map onto the RecordType that can hold them, so a screen created by hand with
+/// INTEGER columns isn't reported as narrowed when reimported as BIGINT.
+fn record_type(data_type: &str) -> RecordType {
+    match data_type {
+        "smallint" | "integer" | "bigint" => RecordType::BIGINT,
+        "real" | "double precision" | "numeric" => RecordType::DOUBLE,
+        _ => RecordType::TEXT,
+    }
+}
+
+/// The csv columns of an existing table, leaving out the ones process_csv adds itself.
+pub async fn existing_columns(
+    conn: &mut PgConnection,
+    table_name: &str,
+) -> Result<Vec<(String, RecordType)>> {
+    let columns: Vec<(String, String)> = sqlx::query_as(
+        r#"
+        SELECT column_name, data_type
+        FROM information_schema.columns
+        WHERE table_name = $1
+        AND table_schema = current_schema()
+        ORDER BY ordinal_position
+        "#,
+    )
+    .bind(table_name)
+    .fetch_all(conn)
+    .await?;
+    Ok(columns
+        .into_iter()
+        .filter(|(col, _)| {
+            col != "id"
+                && col != SOURCE_LINE
+                && col != NUM_PEPTIDES
+                && !CYS_COLUMNS.iter().any(|(cys_col, _)| col == cys_col)
+        })
+        .map(|(col, data_type)| (col, record_type(&data_type)))
+        .collect())
+}
+
+pub fn diff(old: &[(String, RecordType)], new: &[(String, RecordType)]) -> Vec<ColumnChange> {
+    // Postgres folds unquoted identifiers to lower case.
+    let find = |cols: &[(String, RecordType)], col: &str| {
+        cols.iter()
+            .find(|(other, _)| other.eq_ignore_ascii_case(col))
+            .map(|(_, record_type)| *record_type)
+    };
+    let mut changes = vec![];
+    for (col, old_type) in old {
+        match find(new, col) {
+            None => changes.push(ColumnChange::Removed(col.clone(), *old_type)),
+            Some(new_type) if new_type > *old_type => {
+                changes.push(ColumnChange::Widened(col.clone(), *old_type, new_type))
+            }
+            Some(new_type) if new_type < *old_type => {
+                changes.push(ColumnChange::Narrowed(col.clone(), *old_type, new_type))
+            }
+            Some(_) => {}
+        }
+    }
+    for (col, new_type) in new {
+        if find(old, col).is_none() {
+            changes.push(ColumnChange::Added(col.clone(), *new_type));
+        }
+    }
+    changes
+}
+
+pub fn print_summary(table_name: &str, changes: &[ColumnChange]) {
+    if changes.is_empty() {
+        println!("INFO: Schema of {table_name:?} is unchanged.");
+        return;
+    }
+    println!("INFO: Schema changes to {table_name:?}:");
+    for change in changes {
+        let note = if change.is_breaking() {
+            " [breaking]"
+        } else {
+            ""
+        };
+        println!("    {change}{note}");
+    }
+    let removed = changes
+        .iter()
+        .filter(|c| matches!(c, ColumnChange::Removed(..)));
+    let added = changes
+        .iter()
+        .filter(|c| matches!(c, ColumnChange::Added(..)));
+    if removed.count() > 0 && added.count() > 0 {
+        println!("    (columns that were both removed and added may have been renamed)");
+    }
+}
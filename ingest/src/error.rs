@@ -0,0 +1,84 @@
+use crate::formats::Format;
+use crate::rollup::Aggregation;
+use std::io;
+
+/// Why a file could not be read, checked or loaded. Messages are meant to be shown as they are,
+/// e.g. printed by process_csv or returned by the upload page.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("ERROR: Failed to read {path}: {source}")]
+    Read { path: String, source: io::Error },
+    #[error("ERROR: {0}")]
+    Io(#[from] io::Error),
+    #[error("ERROR: Failed to parse the csv: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("ERROR: {0}")]
+    Database(#[from] sqlx::Error),
+
+    #[error("ERROR: Invalid cysteine pattern: {0}")]
+    Regex(#[from] regex::Error),
+    #[error("ERROR: Cysteine pattern {pattern:?} {reason}.")]
+    CysPattern {
+        pattern: String,
+        reason: &'static str,
+    },
+    #[error("ERROR: {format} table has no {column:?} column.")]
+    MissingColumn { format: Format, column: String },
+    // the file doesn't have the layout its format is known by
+    #[error("ERROR: {0}")]
+    Layout(String),
+
+    #[error("ERROR: Failed getting file prefix, pass a table name.")]
+    NoTableName,
+    #[error(
+        "ERROR: table name \"{0}\" has length greater than 63, which is the max allowed by postgrsql."
+    )]
+    TableNameTooLong(String),
+    #[error("ERROR: Invalid column name {column:?}: {reason}.")]
+    InvalidColumn {
+        column: String,
+        reason: &'static str,
+    },
+    #[error("ERROR: Expected {expected} columns, got {got}.")]
+    ColumnCount { expected: usize, got: usize },
+    #[error("ERROR: The csv has no key column {0:?}. Pass --key <column> to choose another.")]
+    NoKeyColumn(String),
+    // every missing and duplicated key, listed with their lines
+    #[error(
+        "ERROR: Key column {column:?} does not identify every row.{report}\nPass --key <column> to choose another key column."
+    )]
+    Keys { column: String, report: String },
+
+    #[error("ERROR: --agg names unknown column {0:?}.")]
+    UnknownAggColumn(String),
+    #[error("ERROR: Can't take the {aggregation} of text column {column:?}.")]
+    Aggregation {
+        aggregation: Aggregation,
+        column: String,
+    },
+    #[error("ERROR: Columns can't be overridden when rolling up.")]
+    RolledUp,
+
+    #[error(
+        "ERROR: Table with name (derived from input file) {0:?} already exists. Pass -f to delete existing table (probably a bad idea)"
+    )]
+    TableExists(String),
+    #[error(
+        "ERROR: Reimporting {0:?} removes or narrows columns. Pass --allow-breaking to replace it anyway."
+    )]
+    Breaking(String),
+    #[error("ERROR: Table {0:?} does not exist, import it before appending to it.")]
+    NoTable(String),
+    #[error(
+        "ERROR: Table {table:?} has no key column {column:?}. Pass --on <column> to join on another column."
+    )]
+    NoJoinColumn { table: String, column: String },
+    #[error("ERROR: Project {0:?} does not exist, create it at /admin/projects first.")]
+    NoProject(String),
+    #[error("ERROR: The csv has no columns besides the key.")]
+    NothingToAppend,
+    #[error("ERROR: Table {table:?} already has columns {columns:?}. Pass -f to replace them.")]
+    ColumnsExist { table: String, columns: Vec<String> },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
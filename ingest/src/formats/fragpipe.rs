@@ -0,0 +1,46 @@
+use super::{Format, Key, Layout, find_column, is_alkylation, require_column, site_id};
+use crate::Result;
+use csv::StringRecord;
+
+pub(super) fn layout(headers: &StringRecord) -> Result<Layout<'static>> {
+    let protein = require_column(headers, "Protein ID", Format::FragPipe)?;
+    let modifications = require_column(headers, "Assigned Modifications", Format::FragPipe)?;
+    // psm.tsv has a row per spectrum, combined_modified_peptide.tsv one per modified peptide.
+    let (start, key) = match find_column(headers, "Spectrum") {
+        Some(spectrum) => (
+            require_column(headers, "Protein Start", Format::FragPipe)?,
+            spectrum,
+        ),
+        None => (
+            require_column(headers, "Start", Format::FragPipe)?,
+            require_column(headers, "Modified Sequence", Format::FragPipe)?,
+        ),
+    };
+    Ok(Layout {
+        sites: Box::new(move |row| {
+            let get = |i| row.get(i).unwrap_or("").trim();
+            let Ok(start) = get(start).parse::<usize>() else {
+                return vec![];
+            };
+            modified_cysteines(get(modifications))
+                .into_iter()
+                .map(|position| site_id(get(protein), start + position - 1))
+                .collect()
+        }),
+        key: Key::Column(key),
+    })
+}
+
+/// The 1-based peptide positions of probe-modified cysteines in assigned modifications such as
+/// `5C(57.0215), 12C(463.2366), N-term(42.0106)`.
+fn modified_cysteines(modifications: &str) -> Vec<usize> {
+    modifications
+        .split(',')
+        .filter_map(|modification| {
+            let (location, mass) = modification.trim().split_once('(')?;
+            let position = location.strip_suffix('C')?.parse::<usize>().ok()?;
+            let mass = mass.trim_end_matches(')');
+            (position > 0 && !is_alkylation(mass)).then_some(position)
+        })
+        .collect()
+}
@@ -0,0 +1,88 @@
+use super::{Format, Key, Layout, Sequences, find_column, is_alkylation, require_column};
+use super::{accession, site_id};
+use crate::{Error, Result};
+use csv::StringRecord;
+
+pub(super) fn layout<'a>(headers: &StringRecord, sequences: &'a Sequences) -> Result<Layout<'a>> {
+    let find = |name| find_column(headers, name);
+    if let (Some(protein), Some(position), Some(amino_acid)) =
+        (find("Protein"), find("Position"), find("Amino acid"))
+    {
+        // A modification site table such as Cys-sites.txt has one row per site.
+        return Ok(Layout {
+            sites: Box::new(move |row| {
+                let get = |i| row.get(i).unwrap_or("").trim();
+                match (get(protein), get(position), get(amino_acid)) {
+                    ("", _, _) | (_, "", _) => vec![],
+                    (protein, position, amino_acid) => {
+                        vec![format!("{}_{amino_acid}{position}", accession(protein))]
+                    }
+                }
+            }),
+            key: Key::Site,
+        });
+    }
+    if find("Modified sequence").is_none() {
+        return Err(Error::Layout(
+            "MaxQuant table is neither evidence.txt nor a modification site table.".to_string(),
+        ));
+    }
+    let sequence = require_column(headers, "Sequence", Format::MaxQuant)?;
+    let modified = require_column(headers, "Modified sequence", Format::MaxQuant)?;
+    let protein = require_column(headers, "Leading razor protein", Format::MaxQuant)?;
+    let id = require_column(headers, "id", Format::MaxQuant)?;
+    if sequences.is_empty() {
+        eprintln!(
+            "WARNING: No protein sequences are loaded, so evidence peptides can't be placed on their proteins. Run process_csv uniprot first."
+        );
+    }
+    Ok(Layout {
+        // evidence.txt doesn't say where a peptide starts, so it is looked up in the sequence.
+        sites: Box::new(move |row| {
+            let get = |i| row.get(i).unwrap_or("").trim();
+            let Some(start) = sequences
+                .get(accession(get(protein)))
+                .and_then(|protein_sequence| protein_sequence.find(get(sequence)))
+            else {
+                return vec![];
+            };
+            modified_cysteines(get(modified))
+                .into_iter()
+                .map(|i| site_id(get(protein), start + i + 1))
+                .collect()
+        }),
+        key: Key::Column(id),
+    })
+}
+
+/// The 0-based positions of probe-modified cysteines in a modified sequence such as
+/// `_AC(Carbamidomethyl (C))DEC(IA-alkyne)K_`. Modification names can contain parentheses.
+fn modified_cysteines(modified: &str) -> Vec<usize> {
+    let mut cysteines = vec![];
+    let mut residues = 0;
+    let mut last = None;
+    let mut chars = modified.trim_matches('_').chars();
+    while let Some(c) = chars.next() {
+        if c == '(' {
+            let mut depth = 1;
+            let name: String = chars
+                .by_ref()
+                .take_while(|c| {
+                    depth += match c {
+                        '(' => 1,
+                        ')' => -1,
+                        _ => 0,
+                    };
+                    depth > 0
+                })
+                .collect();
+            if last == Some('C') && !is_alkylation(&name) {
+                cysteines.push(residues - 1);
+            }
+        } else if c.is_ascii_uppercase() {
+            residues += 1;
+            last = Some(c);
+        }
+    }
+    cysteines
+}
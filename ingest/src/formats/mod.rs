@@ -0,0 +1,316 @@
+//! Readers for search-engine output, which turn it into the same rows a hand-made csv would
+//! give: a cysteine site id column in front of the engine's own columns.
+mod fragpipe;
+mod maxquant;
+mod mztab;
+
+use crate::Result;
+use crate::input::Input;
+use crate::{RecordType, is_reserved};
+use csv::StringRecord;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::Path;
+
+/// Protein sequences by UniProt accession, for formats that don't say where a peptide starts.
+pub type Sequences = HashMap<String, String>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Csv,
+    // evidence.txt or a modification site table such as Cys-sites.txt
+    MaxQuant,
+    // psm.tsv or combined_modified_peptide.tsv
+    FragPipe,
+    MzTab,
+}
+
+impl Format {
+    pub fn from_arg(arg: &str) -> Option<Self> {
+        match arg {
+            "csv" => Some(Format::Csv),
+            "maxquant" => Some(Format::MaxQuant),
+            "fragpipe" => Some(Format::FragPipe),
+            "mztab" => Some(Format::MzTab),
+            _ => None,
+        }
+    }
+
+    /// Guesses the format from the file names the search engines write.
+    pub fn detect(fpath: &Path) -> Self {
+        let name = fpath
+            .file_name()
+            .map_or(String::new(), |name| name.to_string_lossy().to_lowercase());
+        if name.contains(".mztab") {
+            Format::MzTab
+        } else if name.starts_with("evidence") || name.contains("sites.txt") {
+            Format::MaxQuant
+        } else if name.starts_with("psm.tsv") || name.contains("modified_peptide.tsv") {
+            Format::FragPipe
+        } else {
+            Format::Csv
+        }
+    }
+
+    pub fn needs_sequences(&self) -> bool {
+        *self == Format::MaxQuant
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Format::Csv => f.write_str("csv"),
+            Format::MaxQuant => f.write_str("MaxQuant"),
+            Format::FragPipe => f.write_str("FragPipe"),
+            Format::MzTab => f.write_str("mzTab"),
+        }
+    }
+}
+
+/// What a column holds, recorded in screenmap.screen_columns.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Role {
+    Key,
+    Site,
+    Protein,
+    Gene,
+    Peptide,
+    Ratio,
+    Intensity,
+    Count,
+    Score,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Key => "key",
+            Role::Site => "site",
+            Role::Protein => "protein",
+            Role::Gene => "gene",
+            Role::Peptide => "peptide",
+            Role::Ratio => "ratio",
+            Role::Intensity => "intensity",
+            Role::Count => "count",
+            Role::Score => "score",
+        }
+    }
+}
+
+/// The type and role of a search-engine column, by the names the engines give them. The type is
+/// a lower bound, so e.g. an intensity column of whole numbers still loads as DOUBLE PRECISION.
+fn column_rule(header: &str) -> (Option<RecordType>, Option<Role>) {
+    let header = header.to_lowercase();
+    let is = |names: &[&str]| names.contains(&header.as_str());
+    // "Ratio H/L count" counts ratios, so counts come first.
+    if header.ends_with(" count") {
+        (Some(RecordType::BIGINT), Some(Role::Count))
+    } else if header.contains("ratio") {
+        (Some(RecordType::DOUBLE), Some(Role::Ratio))
+    } else if header.contains("intensity") {
+        (Some(RecordType::DOUBLE), Some(Role::Intensity))
+    } else if is(&["score", "pep", "probability", "hyperscore", "expectation"])
+        || header.starts_with("search_engine_score")
+    {
+        (Some(RecordType::DOUBLE), Some(Role::Score))
+    } else if is(&["gene", "gene names"]) {
+        (Some(RecordType::TEXT), Some(Role::Gene))
+    } else if is(&[
+        "protein",
+        "proteins",
+        "protein id",
+        "leading razor protein",
+        "accession",
+    ]) {
+        (Some(RecordType::TEXT), Some(Role::Protein))
+    } else if is(&[
+        "sequence",
+        "peptide",
+        "modified sequence",
+        "modified peptide",
+        "peptide sequence",
+    ]) {
+        (Some(RecordType::TEXT), Some(Role::Peptide))
+    } else if header == "charge" {
+        (Some(RecordType::BIGINT), None)
+    } else {
+        (None, None)
+    }
+}
+
+fn find_column(headers: &StringRecord, name: &str) -> Option<usize> {
+    headers
+        .iter()
+        .position(|h| h.trim().eq_ignore_ascii_case(name))
+}
+
+fn require_column(headers: &StringRecord, name: &str, format: Format) -> Result<usize> {
+    find_column(headers, name).ok_or_else(|| crate::Error::MissingColumn {
+        format,
+        column: name.to_string(),
+    })
+}
+
+/// The accession in `sp|P04637|P53_HUMAN`, or the first of a `;` separated protein list.
+fn accession(protein: &str) -> &str {
+    let protein = protein.split(';').next().unwrap_or("").trim();
+    match protein.split('|').collect::<Vec<_>>()[..] {
+        ["sp" | "tr", accession, ..] => accession,
+        _ => protein,
+    }
+}
+
+fn site_id(protein: &str, residue: usize) -> String {
+    format!("{}_C{residue}", accession(protein))
+}
+
+/// Whether a cysteine modification, by name, UNIMOD id or mass, is the iodoacetamide capping
+/// every cysteine rather than the probe.
+fn is_alkylation(modification: &str) -> bool {
+    let modification = modification.trim().to_lowercase();
+    if modification.contains("carbamidomethyl")
+        || modification == "ca"
+        || modification == "unimod:4"
+    {
+        return true;
+    }
+    modification
+        .trim_start_matches("chemmod:")
+        .trim_start_matches('+')
+        .parse::<f64>()
+        .is_ok_and(|mass| (mass - 57.02146).abs() < 0.01)
+}
+
+/// How a row is identified. Search engines report many rows per site, so only site tables are
+/// keyed by their site.
+enum Key {
+    Column(usize),
+    Site,
+    // The fields joined with '|', in a derived row_key column.
+    Joined(Vec<usize>),
+}
+
+type Records<'a> = Box<dyn Iterator<Item = csv::Result<StringRecord>> + Send + 'a>;
+// ids of the probe-modified cysteines of a row, e.g. P04637_C176
+type Sites<'a> = Box<dyn Fn(&StringRecord) -> Vec<String> + Send + 'a>;
+
+/// Where a format finds the sites and key of its rows.
+struct Layout<'a> {
+    sites: Sites<'a>,
+    key: Key,
+}
+
+/// The rows of an input as they are loaded, with the type and role known for each column.
+pub struct Rows<'a> {
+    pub headers: Vec<String>,
+    pub types: Vec<Option<RecordType>>,
+    pub roles: Vec<Option<Role>>,
+    // the format's key column, used unless --key is passed
+    pub key: Option<usize>,
+    // the column of cysteine identifiers, if not the key
+    pub site: Option<usize>,
+    // lines describing the run, stored with the screen's preamble
+    pub metadata: Vec<String>,
+    pub records: Records<'a>,
+}
+
+pub struct FormatReader {
+    pub format: Format,
+    sequences: Sequences,
+}
+
+impl FormatReader {
+    pub fn new(format: Format, sequences: Sequences) -> Self {
+        Self { format, sequences }
+    }
+
+    /// Reads `input`, which is read twice, once to infer the column types and once to load it.
+    pub fn rows(&self, input: &Input) -> Result<Rows<'_>> {
+        if self.format == Format::MzTab {
+            let (headers, metadata, records) = mztab::psm_section(input)?;
+            let layout = mztab::layout(&headers)?;
+            return Ok(derive(self.format, headers, metadata, layout, records));
+        }
+        let mut reader = input.reader()?;
+        let headers = reader.headers()?.clone();
+        let records = Box::new(reader.into_records());
+        let layout = match self.format {
+            Format::Csv => {
+                let num_headers = headers.len();
+                return Ok(Rows {
+                    headers: headers.iter().map(str::to_string).collect(),
+                    types: vec![None; num_headers],
+                    roles: vec![None; num_headers],
+                    key: None,
+                    site: None,
+                    metadata: vec![],
+                    records,
+                });
+            }
+            Format::MaxQuant => maxquant::layout(&headers, &self.sequences)?,
+            Format::FragPipe => fragpipe::layout(&headers)?,
+            Format::MzTab => unreachable!(),
+        };
+        Ok(derive(self.format, headers, vec![], layout, records))
+    }
+}
+
+/// Puts the site, sites and, for joined keys, row_key columns in front of the engine's own.
+fn derive<'a>(
+    format: Format,
+    headers: StringRecord,
+    metadata: Vec<String>,
+    layout: Layout<'a>,
+    records: Records<'a>,
+) -> Rows<'a> {
+    let mut names = vec!["site".to_string(), "sites".to_string()];
+    let mut types = vec![Some(RecordType::TEXT); 2];
+    let mut roles = vec![Some(Role::Site), None];
+    if let Key::Joined(_) = layout.key {
+        names.push("row_key".to_string());
+        types.push(Some(RecordType::TEXT));
+        roles.push(None);
+    }
+    let num_derived = names.len();
+    let key = match &layout.key {
+        Key::Column(i) => *i + num_derived,
+        Key::Site => 0,
+        Key::Joined(_) => 2,
+    };
+    for header in headers.iter() {
+        let (record_type, role) = column_rule(header);
+        // MaxQuant numbers its rows in an id column, which would clash with the screen's own.
+        if is_reserved(header) {
+            names.push(format!("{}_{header}", format.to_string().to_lowercase()));
+        } else {
+            names.push(header.to_string());
+        }
+        types.push(record_type);
+        roles.push(role);
+    }
+
+    let records = records.map(move |row| {
+        let row = row?;
+        let sites = (layout.sites)(&row);
+        let mut derived = StringRecord::new();
+        derived.push_field(sites.first().map_or("", String::as_str));
+        derived.push_field(&sites.join(";"));
+        if let Key::Joined(idxs) = &layout.key {
+            let fields: Vec<_> = idxs.iter().map(|i| row.get(*i).unwrap_or("")).collect();
+            derived.push_field(&fields.join("|"));
+        }
+        derived.extend(row.iter());
+        derived.set_position(row.position().cloned());
+        Ok(derived)
+    });
+    Rows {
+        headers: names,
+        types,
+        roles,
+        key: Some(key),
+        site: Some(0),
+        metadata,
+        records: Box::new(records),
+    }
+}
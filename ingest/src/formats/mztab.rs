@@ -0,0 +1,78 @@
+use super::{Format, Key, Layout, Records, is_alkylation, require_column, site_id};
+use crate::input::Input;
+use crate::{Error, Result};
+use csv::StringRecord;
+
+/// The header and rows of the PSM section, without their PSH and PSM prefixes, and the MTD lines
+/// describing the run.
+pub(super) fn psm_section(input: &Input) -> Result<(StringRecord, Vec<String>, Records<'static>)> {
+    // Every section has its own columns, so the lines differ in length.
+    let mut records = input.flexible_reader()?.into_records();
+    let mut metadata = vec![];
+    let headers = loop {
+        let Some(row) = records.next() else {
+            return Err(Error::Layout("mzTab file has no PSM section.".to_string()));
+        };
+        let row = row?;
+        match row.get(0) {
+            Some("MTD") => metadata.push(row.iter().skip(1).collect::<Vec<_>>().join("\t")),
+            Some("PSH") => break strip_prefix(&row),
+            _ => {}
+        }
+    };
+    let records = records.filter_map(|row| match row {
+        Ok(row) if row.get(0) == Some("PSM") => Some(Ok(strip_prefix(&row))),
+        Ok(_) => None,
+        Err(e) => Some(Err(e)),
+    });
+    Ok((headers, metadata, Box::new(records)))
+}
+
+fn strip_prefix(row: &StringRecord) -> StringRecord {
+    let mut stripped: StringRecord = row.iter().skip(1).collect();
+    stripped.set_position(row.position().cloned());
+    stripped
+}
+
+pub(super) fn layout(headers: &StringRecord) -> Result<Layout<'static>> {
+    let sequence = require_column(headers, "sequence", Format::MzTab)?;
+    let accession = require_column(headers, "accession", Format::MzTab)?;
+    let modifications = require_column(headers, "modifications", Format::MzTab)?;
+    let start = require_column(headers, "start", Format::MzTab)?;
+    // A PSM matching several proteins is repeated once per protein.
+    let psm_id = require_column(headers, "PSM_ID", Format::MzTab)?;
+    Ok(Layout {
+        sites: Box::new(move |row| {
+            let get = |i| row.get(i).unwrap_or("").trim();
+            let Ok(start) = get(start).parse::<usize>() else {
+                return vec![];
+            };
+            modified_cysteines(get(sequence), get(modifications))
+                .into_iter()
+                .map(|position| site_id(get(accession), start + position - 1))
+                .collect()
+        }),
+        key: Key::Joined(vec![psm_id, accession]),
+    })
+}
+
+/// The 1-based positions of probe-modified cysteines in modifications such as
+/// `3-UNIMOD:4,7-CHEMMOD:+463.2366`. Ambiguous positions (`3|7-...`) count as the first.
+fn modified_cysteines(sequence: &str, modifications: &str) -> Vec<usize> {
+    if modifications == "null" {
+        return vec![];
+    }
+    modifications
+        .split(',')
+        .filter_map(|modification| {
+            let (position, name) = modification.trim().split_once('-')?;
+            let digits = position.len()
+                - position
+                    .trim_start_matches(|c: char| c.is_ascii_digit())
+                    .len();
+            let position = position[..digits].parse::<usize>().ok()?;
+            let residue = sequence.chars().nth(position.checked_sub(1)?)?;
+            (residue == 'C' && !is_alkylation(name)).then_some(position)
+        })
+        .collect()
+}
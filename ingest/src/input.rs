@@ -0,0 +1,315 @@
+use crate::{Error, Result};
+use encoding_rs::{Encoding, UTF_8, UTF_16BE, UTF_16LE, WINDOWS_1252};
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+const SNIFF_BYTES: u64 = 1 << 16;
+const SNIFF_RECORDS: usize = 1000;
+const DELIMITERS: &[u8] = b",;\t";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl Compression {
+    fn sniff(magic: &[u8]) -> Self {
+        if magic.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Compression::Zstd
+        } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Compression::Xz
+        } else {
+            Compression::None
+        }
+    }
+}
+
+enum Source {
+    File(PathBuf),
+    // stdin can only be read once, so it is decompressed to disk for the inference and load passes.
+    Stdin(NamedTempFile),
+}
+
+/// A csv file or stdin with its compression, encoding and delimiter, either given on the command
+/// line or detected. Every reader it hands out yields decompressed utf-8 with any BOM stripped.
+pub struct Input {
+    source: Source,
+    pub compression: Compression,
+    pub encoding: &'static Encoding,
+    pub delimiter: u8,
+    // metadata lines before the header, as written by instrument exports
+    skip_lines: usize,
+    // header rows merged into one column name each, e.g. compound over concentration
+    header_rows: usize,
+}
+
+impl Input {
+    /// Opens `fpath`, or stdin if it is "-", whose header starts after `skip_lines` lines and
+    /// spans `header_rows` rows.
+    pub fn open(
+        fpath: PathBuf,
+        encoding: Option<&'static Encoding>,
+        delimiter: Option<u8>,
+        skip_lines: usize,
+        header_rows: usize,
+    ) -> Result<Self> {
+        let (source, compression) = if fpath.as_os_str() == "-" {
+            let read_error = |source| Error::Read {
+                path: "stdin".to_string(),
+                source,
+            };
+            let mut stdin = io::stdin();
+            let mut magic = vec![];
+            stdin
+                .by_ref()
+                .take(6)
+                .read_to_end(&mut magic)
+                .map_err(read_error)?;
+            let compression = Compression::sniff(&magic);
+            let mut spool = NamedTempFile::new()?;
+            let mut decompressed = decompress(compression, io::Cursor::new(magic).chain(stdin))?;
+            io::copy(&mut decompressed, &mut spool).map_err(read_error)?;
+            (Source::Stdin(spool), compression)
+        } else {
+            (Source::File(fpath), Compression::None)
+        };
+        let mut input = Input {
+            source,
+            compression,
+            encoding: UTF_8,
+            delimiter: b',',
+            skip_lines,
+            header_rows,
+        };
+        if let Source::File(_) = input.source {
+            let mut magic = vec![];
+            input.file()?.take(6).read_to_end(&mut magic)?;
+            input.compression = Compression::sniff(&magic);
+        }
+        input.encoding = match encoding {
+            Some(encoding) => encoding,
+            None => sniff_encoding(&input.head()?),
+        };
+        input.delimiter = match delimiter {
+            Some(delimiter) => delimiter,
+            None => input.sniff_delimiter()?,
+        };
+        Ok(input)
+    }
+
+    /// The path read from, which for stdin is the spooled, decompressed copy.
+    pub fn path(&self) -> &Path {
+        match &self.source {
+            Source::File(fpath) => fpath,
+            Source::Stdin(spool) => spool.path(),
+        }
+    }
+
+    /// The file name up to its first '.', or None for stdin.
+    pub fn file_prefix(&self) -> Option<&str> {
+        match &self.source {
+            Source::File(fpath) => fpath.file_prefix()?.to_str(),
+            Source::Stdin(_) => None,
+        }
+    }
+
+    pub fn display_name(&self) -> String {
+        match &self.source {
+            Source::File(fpath) => fpath.display().to_string(),
+            Source::Stdin(_) => "stdin".to_string(),
+        }
+    }
+
+    fn file(&self) -> Result<File> {
+        File::open(self.path()).map_err(|source| Error::Read {
+            path: self.display_name(),
+            source,
+        })
+    }
+
+    fn decompressed(&self) -> Result<Box<dyn Read + Send>> {
+        match self.source {
+            Source::File(_) => decompress(self.compression, self.file()?),
+            Source::Stdin(_) => Ok(Box::new(self.file()?)),
+        }
+    }
+
+    fn head(&self) -> Result<Vec<u8>> {
+        let mut head = vec![];
+        self.decompressed()?
+            .take(SNIFF_BYTES)
+            .read_to_end(&mut head)?;
+        Ok(head)
+    }
+
+    pub fn decoded(&self) -> Result<impl Read + use<>> {
+        // A BOM overrides the configured encoding, and is stripped either way.
+        Ok(DecodeReaderBytesBuilder::new()
+            .encoding(Some(self.encoding))
+            .bom_override(true)
+            .build(self.decompressed()?))
+    }
+
+    /// The decoded text after the preamble.
+    fn body(&self) -> Result<impl BufRead + use<>> {
+        let mut body = BufReader::new(self.decoded()?);
+        for _ in 0..self.skip_lines {
+            body.read_line(&mut String::new())?;
+        }
+        Ok(body)
+    }
+
+    /// The lines before the header, without their line endings.
+    pub fn preamble(&self) -> Result<Vec<String>> {
+        BufReader::new(self.decoded()?)
+            .lines()
+            .take(self.skip_lines)
+            .map(|line| Ok(line?))
+            .collect()
+    }
+
+    /// A csv reader over the body whose headers are the merged header rows.
+    pub fn reader(&self) -> Result<csv::Reader<impl Read + use<>>> {
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .has_headers(self.header_rows == 1)
+            .from_reader(self.body()?);
+        if self.header_rows != 1 {
+            let header_rows = reader
+                .records()
+                .take(self.header_rows)
+                .collect::<csv::Result<Vec<_>>>()?;
+            reader.set_headers(merge_headers(&header_rows));
+        }
+        Ok(reader)
+    }
+
+    /// A csv reader over the body that returns every line as a record, whatever its length.
+    pub fn flexible_reader(&self) -> Result<csv::Reader<impl Read + use<>>> {
+        Ok(csv::ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(self.body()?))
+    }
+
+    /// The line of the file `row` starts on, counting the preamble.
+    pub fn line(&self, row: &csv::StringRecord) -> u64 {
+        row.position().map_or(0, |pos| pos.line()) + self.skip_lines as u64
+    }
+
+    /// The delimiter that occurs most often in the header line.
+    fn sniff_delimiter(&self) -> Result<u8> {
+        let mut header = String::new();
+        self.body()?.read_line(&mut header)?;
+        Ok(*DELIMITERS
+            .iter()
+            .max_by_key(|d| header.bytes().filter(|b| b == *d).count())
+            .unwrap())
+    }
+
+    /// Guesses ',' as the decimal separator if more of the first records' fields look like
+    /// comma decimals than dot decimals. Files delimited by ',' always get '.'.
+    pub fn sniff_decimal(&self) -> Result<char> {
+        if self.delimiter == b',' {
+            return Ok('.');
+        }
+        let (mut commas, mut dots) = (0, 0);
+        for row in self
+            .reader()?
+            .records()
+            .take(SNIFF_RECORDS)
+            .flatten()
+        {
+            for item in row.iter() {
+                if looks_like_decimal(item, ',') {
+                    commas += 1;
+                } else if looks_like_decimal(item, '.') {
+                    dots += 1;
+                }
+            }
+        }
+        Ok(if commas > dots { ',' } else { '.' })
+    }
+}
+
+fn decompress(
+    compression: Compression,
+    reader: impl Read + Send + 'static,
+) -> Result<Box<dyn Read + Send>> {
+    Ok(match compression {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+        Compression::Zstd => Box::new(zstd::stream::read::Decoder::new(reader)?),
+        Compression::Xz => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+    })
+}
+
+/// Joins each column's header rows with '_'. Rows above the last are forward filled, since
+/// spreadsheets leave the cells a merged header spans empty.
+fn merge_headers(rows: &[csv::StringRecord]) -> csv::StringRecord {
+    let num_cols = rows.iter().map(csv::StringRecord::len).max().unwrap_or(0);
+    let mut names = vec![vec![]; num_cols];
+    for (i, row) in rows.iter().enumerate() {
+        let mut fill = "";
+        for (col, name) in names.iter_mut().enumerate() {
+            let item = row.get(col).unwrap_or("").trim();
+            if !item.is_empty() || i + 1 == rows.len() {
+                fill = item;
+            }
+            if !fill.is_empty() {
+                name.push(fill);
+            }
+        }
+    }
+    names.iter().map(|name| name.join("_")).collect()
+}
+
+fn sniff_encoding(head: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(head) {
+        return encoding;
+    }
+    // Without a BOM, utf-16 still gives away ascii text with a zero byte per character.
+    let zeros_at = |offset| {
+        head.iter()
+            .skip(offset)
+            .step_by(2)
+            .filter(|b| **b == 0)
+            .count()
+    };
+    if zeros_at(1) > head.len() / 4 {
+        return UTF_16LE;
+    } else if zeros_at(0) > head.len() / 4 {
+        return UTF_16BE;
+    }
+    match std::str::from_utf8(head) {
+        // error_len is None when the head just cuts a character in half.
+        Err(e) if e.error_len().is_some() => WINDOWS_1252,
+        _ => UTF_8,
+    }
+}
+
+fn looks_like_decimal(item: &str, separator: char) -> bool {
+    let item = item.trim().trim_start_matches(['+', '-']);
+    item.split_once(separator).is_some_and(|(int, frac)| {
+        int.chars().all(|c| c.is_ascii_digit())
+            && !frac.is_empty()
+            && frac.chars().all(|c| c.is_ascii_digit())
+    })
+}
+
+pub fn parse_delimiter(arg: &str) -> Option<u8> {
+    match arg {
+        "tab" | "\\t" | "\t" => Some(b'\t'),
+        _ if arg.len() == 1 && arg.is_ascii() => Some(arg.as_bytes()[0]),
+        _ => None,
+    }
+}
@@ -0,0 +1,61 @@
+use crate::{Error, Result};
+use std::collections::HashMap;
+
+// How many offending keys are listed before the rest are only counted.
+const REPORT_LIMIT: usize = 20;
+
+/// Collects the line numbers of every key value so missing and duplicate keys can be reported
+/// together once the whole file has been read.
+#[derive(Debug, Default)]
+pub struct KeyCheck {
+    lines: HashMap<String, Vec<u64>>,
+    null_lines: Vec<u64>,
+}
+
+impl KeyCheck {
+    /// Records the key on `line`, or None if it is missing.
+    pub fn add(&mut self, key: Option<&str>, line: u64) {
+        match key {
+            Some(key) => self.lines.entry(key.to_string()).or_default().push(line),
+            None => self.null_lines.push(line),
+        }
+    }
+
+    /// Fails with every missing and duplicated key if `column` can't identify rows.
+    pub fn validate(&self, column: &str) -> Result<()> {
+        let mut duplicates: Vec<_> = self
+            .lines
+            .iter()
+            .filter(|(_, lines)| lines.len() > 1)
+            .collect();
+        if duplicates.is_empty() && self.null_lines.is_empty() {
+            return Ok(());
+        }
+        duplicates.sort_by_key(|(_, lines)| lines[0]);
+
+        let mut report = String::new();
+        if !self.null_lines.is_empty() {
+            report.push_str(&format!(
+                "\n    missing on {} lines: {:?}",
+                self.null_lines.len(),
+                &self.null_lines[..self.null_lines.len().min(REPORT_LIMIT)]
+            ));
+        }
+        if !duplicates.is_empty() {
+            report.push_str(&format!("\n    {} duplicated keys:", duplicates.len()));
+            for (key, lines) in duplicates.iter().take(REPORT_LIMIT) {
+                report.push_str(&format!("\n    {key:?} on lines {lines:?}"));
+            }
+            if duplicates.len() > REPORT_LIMIT {
+                report.push_str(&format!(
+                    "\n    ... and {} more",
+                    duplicates.len() - REPORT_LIMIT
+                ));
+            }
+        }
+        Err(Error::Keys {
+            column: column.to_string(),
+            report,
+        })
+    }
+}
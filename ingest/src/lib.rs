@@ -0,0 +1,537 @@
+#![feature(path_file_prefix)]
+//! Type inference and loading of screens into Postgres, shared by the process_csv command and the
+//! screenmap server's upload page.
+//!
+//! A file is opened with [`Input::open`], after which [`CSVProcessor::new`] infers its columns and
+//! checks its key. [`CSVProcessor::write_db`] loads it as a new or replaced table, or
+//! [`append_db`](CSVProcessor::append_db) and [`write_rollup`](CSVProcessor::write_rollup) for the
+//! other import modes, and [`CSVProcessor::record`] catalogs the import in screenmap.screens. They
+//! all run in a transaction the caller commits once the import is complete.
+mod append;
+pub mod audit;
+pub mod catalog;
+pub mod cys_id;
+mod drift;
+mod error;
+pub mod formats;
+pub mod input;
+mod key;
+mod preview;
+pub mod rollup;
+pub mod uniprot;
+pub mod values;
+
+use cys_id::{CYS_COLUMNS, CysIdParser, CysSite};
+use formats::{FormatReader, Role, Rows};
+use input::Input;
+use key::KeyCheck;
+use rollup::{Aggregation, NUM_PEPTIDES, Rollup};
+use sqlx::{Connection, PgConnection, PgPool};
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use values::ValueParser;
+
+pub use error::{Error, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum RecordType {
+    // rank by MAX - MIN
+    BIGINT = 0,
+    DOUBLE = 1,
+    TEXT = 2,
+}
+
+impl Display for RecordType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecordType::BIGINT => f.write_str("BIGINT"),
+            RecordType::DOUBLE => f.write_str("DOUBLE PRECISION"),
+            RecordType::TEXT => f.write_str("TEXT"),
+        }
+    }
+}
+
+pub const SOURCE_LINE: &str = "source_line";
+
+const SCHEMA: &str = include_str!("../../schema.sql");
+
+pub async fn ensure_schema(pool: &PgPool) -> Result<()> {
+    sqlx::raw_sql(SCHEMA).execute(pool).await?;
+    Ok(())
+}
+
+pub struct CSVProcessor {
+    pub table_name: String,
+    // the headers as they are in the file, before they are made valid column names
+    pub headers: Vec<String>,
+    // the types of the screen's columns, which differ from input_types when rolling up
+    pub header_types: Vec<RecordType>,
+    input_types: Vec<RecordType>,
+    pub columns: Vec<String>,
+    // index of the column that identifies rows
+    pub key_idx: usize,
+    // index of the column parsed as a cysteine identifier, the key unless the format has a site column
+    site_idx: usize,
+    roles: Vec<Option<Role>>,
+    // run description from the format, stored with the preamble
+    pub metadata: Vec<String>,
+    input: Input,
+    format_reader: FormatReader,
+    cys_sites: Vec<(String, CysSite)>,
+    value_parser: ValueParser,
+    // how each column is aggregated per site, None on the site column, if rolling up
+    aggregations: Option<Vec<Option<Aggregation>>>,
+    // rows copied so far, for whoever runs the import to report
+    progress: Option<Arc<AtomicU64>>,
+}
+
+/// Postgres keywords that can't be column names without quoting: the reserved ones and those
+/// that can only name types and functions, as `pg_get_keywords()` lists them with catcode R and T.
+const SQL_KEYWORDS: &[&str] = &[
+    "all", "analyse", "analyze", "and", "any", "array", "as", "asc", "asymmetric", "authorization",
+    "binary", "both", "case", "cast", "check", "collate", "collation", "column", "concurrently",
+    "constraint", "create", "cross", "current_catalog", "current_date", "current_role",
+    "current_schema", "current_time", "current_timestamp", "current_user", "default", "deferrable",
+    "desc", "distinct", "do", "else", "end", "except", "false", "fetch", "for", "foreign", "freeze",
+    "from", "full", "grant", "group", "having", "ilike", "in", "initially", "inner", "intersect",
+    "into", "is", "isnull", "join", "lateral", "leading", "left", "like", "limit", "localtime",
+    "localtimestamp", "natural", "not", "notnull", "null", "offset", "on", "only", "or", "order",
+    "outer", "overlaps", "placing", "primary", "references", "returning", "right", "select",
+    "session_user", "similar", "some", "symmetric", "table", "tablesample", "then", "to",
+    "trailing", "true", "union", "unique", "user", "using", "variadic", "verbose", "when", "where",
+    "window", "with",
+];
+
+/// Whether `col` is one of the columns process_csv adds to every screen.
+pub fn is_reserved(col: &str) -> bool {
+    col.eq_ignore_ascii_case("id")
+        || col.eq_ignore_ascii_case(SOURCE_LINE)
+        || col.eq_ignore_ascii_case(NUM_PEPTIDES)
+        || CYS_COLUMNS.iter().any(|(cys_col, _)| col.eq_ignore_ascii_case(cys_col))
+}
+
+pub trait ValidateForSQL {
+    fn validate(&self) -> String;
+}
+impl ValidateForSQL for &str {
+    fn validate(&self) -> String {
+        if is_reserved(self) {
+            eprintln!("ERROR: use of reserved word '{self}' in csv.")
+        }
+        // Search engines use any punctuation in their headers, e.g. "Ratio H/L" or "[ppm]".
+        let mut col = self.replace(|c: char| !c.is_alphanumeric() && c != '_', "_");
+        if SQL_KEYWORDS.contains(&col.to_ascii_lowercase().as_str()) {
+            col.push('_');
+        }
+        col
+    }
+}
+
+/// `table`, or the input's file name up to its first '.'.
+pub fn table_name(input: &Input, table: Option<&str>) -> Result<String> {
+    validate_table_name(table.or(input.file_prefix()).ok_or(Error::NoTableName)?)
+}
+
+/// `name` made a valid table name the way process_csv names screens.
+pub fn validate_table_name(name: &str) -> Result<String> {
+    let table_name = name.validate().to_ascii_lowercase();
+    if table_name.is_empty() {
+        return Err(Error::NoTableName);
+    }
+    if table_name.len() > 63 {
+        return Err(Error::TableNameTooLong(table_name));
+    }
+    Ok(table_name)
+}
+
+impl CSVProcessor {
+    /// Infers the column types and checks that `key`, or the first column if None, has a unique
+    /// value on every row. When rolling up, rows are keyed by their site instead.
+    pub fn new(
+        input: Input,
+        table_name: String,
+        key: Option<&str>,
+        format_reader: FormatReader,
+        cys_parser: &CysIdParser,
+        value_parser: ValueParser,
+        rollup: Option<&Rollup>,
+    ) -> Result<Self> {
+        let Rows {
+            headers,
+            types: known_types,
+            roles,
+            key: format_key,
+            site: format_site,
+            metadata,
+            records,
+        } = format_reader.rows(&input)?;
+        let columns: Vec<_> = headers.iter().map(|s| s.as_str().validate()).collect();
+        let key_idx = match key {
+            // Postgres folds unquoted identifiers to lower case.
+            Some(key) => columns
+                .iter()
+                .position(|col| col.eq_ignore_ascii_case(&key.validate()))
+                .ok_or_else(|| Error::NoKeyColumn(key.to_string()))?,
+            None => format_key.unwrap_or(0),
+        };
+        let site_idx = format_site.unwrap_or(key_idx);
+        // Each site is one row of the screen, so the site is its key.
+        let key_idx = if rollup.is_some() { site_idx } else { key_idx };
+        let num_headers = headers.len();
+        let mut header_types = vec![None; num_headers];
+        let mut key_check = KeyCheck::default();
+        let mut cys_sites = vec![];
+        let mut parsed_ids = HashSet::new();
+        let mut unparsed_ids = vec![];
+        for (i, maybe_row) in records.enumerate() {
+            if let Err(e) = maybe_row {
+                eprintln!("WARNING: Error parsing csv on line {}: {:?}", i + 1, e);
+            } else if let Ok(row) = maybe_row {
+                let key = row.get(key_idx).filter(|id| !value_parser.is_null(id));
+                if rollup.is_none() {
+                    key_check.add(key, input.line(&row));
+                }
+                // Search-engine rows repeat a site for every peptide or spectrum it was seen in.
+                let cys_id = row.get(site_idx).filter(|id| !value_parser.is_null(id));
+                if let Some(cys_id) = cys_id.filter(|id| !parsed_ids.contains(*id)) {
+                    match cys_parser.parse(cys_id) {
+                        Some(site) => {
+                            parsed_ids.insert(cys_id.to_string());
+                            cys_sites.push((cys_id.to_string(), site));
+                        }
+                        None => unparsed_ids.push(cys_id.to_string()),
+                    }
+                }
+                for (maybe_header_type, item) in header_types.iter_mut().zip(row.iter()) {
+                    let Some(smallest_type) = value_parser.record_type(item) else {
+                        continue;
+                    };
+                    if let Some(header_type) = maybe_header_type {
+                        if smallest_type > *header_type {
+                            *header_type = smallest_type
+                        }
+                    } else {
+                        *maybe_header_type = Some(smallest_type);
+                    }
+                }
+            }
+        }
+
+        key_check.validate(&headers[key_idx])?;
+        for c in columns.iter() {
+            if c.is_empty() {
+                return Err(Error::InvalidColumn {
+                    column: c.clone(),
+                    reason: "it is empty",
+                });
+            } else if c.starts_with(|c: char| c.is_ascii_digit()) {
+                return Err(Error::InvalidColumn {
+                    column: c.clone(),
+                    reason: "it starts with a digit",
+                });
+            }
+        }
+        // Types the format knows for a column only ever widen the inferred one.
+        let header_types: Vec<_> = header_types
+            .into_iter()
+            .zip(known_types)
+            .map(|(header_type, known_type)| match (header_type, known_type) {
+                (Some(header_type), Some(known_type)) if known_type > header_type => {
+                    Some(known_type)
+                }
+                (header_type, known_type) => header_type.or(known_type),
+            })
+            .zip(headers.iter())
+            .map(|(header_type, header)| {
+                header_type.unwrap_or_else(|| {
+                    eprintln!("WARNING: Column {header:?} has no values, loading it as TEXT.");
+                    RecordType::TEXT
+                })
+            })
+            .collect();
+        println!("CSV header -> SQL column: Detected Type");
+        headers
+            .iter()
+            .zip(columns.iter())
+            .zip(header_types.iter())
+            .for_each(|((header, column), header_type)| {
+                println!("{header} -> {column}: {header_type}")
+            });
+        let input_types = header_types.clone();
+        let (header_types, aggregations) = match rollup {
+            Some(rollup) => {
+                let resolved = rollup.resolve(&headers, &columns, &input_types, site_idx)?;
+                println!("SQL column: Aggregation -> Type");
+                for (column, (aggregation, result_type)) in columns.iter().zip(resolved.iter()) {
+                    match aggregation {
+                        Some(aggregation) => println!("{column}: {aggregation} -> {result_type}"),
+                        None => println!("{column}: site -> {result_type}"),
+                    }
+                }
+                let (aggregations, header_types) = resolved.into_iter().unzip();
+                (header_types, Some(aggregations))
+            }
+            None => (input_types.clone(), None),
+        };
+        println!(
+            "INFO: Parsed {} of {} cysteine identifiers in column {:?}.",
+            cys_sites.len(),
+            cys_sites.len() + unparsed_ids.len(),
+            headers[site_idx]
+        );
+        if !unparsed_ids.is_empty() {
+            eprintln!(
+                "WARNING: Could not parse cysteine identifiers, e.g. {:?}. Pass --cys-pattern to add a pattern.",
+                &unparsed_ids[..unparsed_ids.len().min(5)]
+            );
+        }
+
+        Ok(Self {
+            table_name,
+            headers,
+            columns,
+            header_types,
+            input_types,
+            key_idx,
+            site_idx,
+            roles,
+            metadata,
+            input,
+            format_reader,
+            cys_sites,
+            value_parser,
+            aggregations,
+            progress: None,
+        })
+    }
+
+    /// Loads the file as a new table, or with `force` replaces the existing one, and returns
+    /// whether it did the latter. Pass a transaction, so that a failed load leaves the existing
+    /// table as it was.
+    pub async fn write_db(
+        &self,
+        conn: &mut PgConnection,
+        force: bool,
+        allow_breaking: bool,
+    ) -> Result<bool> {
+        let table_exists: bool =
+            sqlx::query_scalar("SELECT EXISTS (SELECT FROM pg_tables WHERE tablename = $1)")
+                .bind(&self.table_name)
+                .fetch_one(&mut *conn)
+                .await?;
+        if table_exists {
+            if force {
+                let old_columns = drift::existing_columns(conn, &self.table_name).await?;
+                let new_columns: Vec<_> = self
+                    .columns
+                    .iter()
+                    .cloned()
+                    .zip(self.header_types.iter().copied())
+                    .collect();
+                let changes = drift::diff(&old_columns, &new_columns);
+                drift::print_summary(&self.table_name, &changes);
+                if changes.iter().any(drift::ColumnChange::is_breaking) && !allow_breaking {
+                    return Err(Error::Breaking(self.table_name.clone()));
+                }
+                let drop_table_q = format!("DROP TABLE {}", self.table_name);
+                println!("INFO: Running SQL query \"{drop_table_q}\";");
+                sqlx::query(&drop_table_q).execute(&mut *conn).await?;
+            } else {
+                return Err(Error::TableExists(self.table_name.clone()));
+            }
+        }
+
+        if let Some(aggregations) = &self.aggregations {
+            self.write_rollup(conn, aggregations).await?;
+        } else {
+            let mut create_tbl_q = self.columns.iter().zip(self.header_types.iter()).fold(
+                format!("CREATE TABLE {}(", self.table_name),
+                |acc, (header, header_type)| format!("{acc}{header} {header_type},"),
+            );
+            create_tbl_q.push_str(&format!("{SOURCE_LINE} BIGINT,"));
+            for (col, col_type) in CYS_COLUMNS {
+                create_tbl_q.push_str(&format!("{col} {col_type},"));
+            }
+            create_tbl_q.push_str("id INTEGER)");
+            println!("INFO: Running SQL query \"{create_tbl_q}\";");
+            sqlx::query(&create_tbl_q).execute(&mut *conn).await?;
+
+            self.copy_rows(conn, &self.table_name).await?;
+        }
+
+        let add_id_q = format!("ALTER TABLE {} ADD PRIMARY KEY (id)", self.table_name);
+        println!("INFO: Running SQL query \"{add_id_q}\";");
+        sqlx::query(&add_id_q).execute(&mut *conn).await?;
+
+        self.write_cys_sites(conn).await?;
+        self.create_indexes(conn, 0..self.columns.len()).await?;
+
+        Ok(table_exists)
+    }
+
+    /// Counts the rows copied into `progress` as they are sent to the database.
+    pub fn report_progress(&mut self, progress: Arc<AtomicU64>) {
+        self.progress = Some(progress);
+    }
+
+    /// The role of every column the format knows, and of the key and site columns.
+    pub fn column_roles(&self) -> Vec<(String, &'static str)> {
+        let mut roles: Vec<_> = self
+            .columns
+            .iter()
+            .zip(self.roles.iter())
+            .filter_map(|(col, role)| Some((col.to_ascii_lowercase(), role.as_ref()?.as_str())))
+            .collect();
+        for (i, role) in [(self.key_idx, Role::Key), (self.site_idx, Role::Site)] {
+            let col = self.columns[i].to_ascii_lowercase();
+            if !roles.contains(&(col.clone(), role.as_str())) {
+                roles.push((col, role.as_str()));
+            }
+        }
+        if self.aggregations.is_some() {
+            roles.push((NUM_PEPTIDES.to_string(), Role::Count.as_str()));
+        }
+        roles
+    }
+
+    /// Indexes the identifier and the columns at `col_idxs` for the server's search_table, then
+    /// analyzes the table so the planner knows to use them.
+    async fn create_indexes(
+        &self,
+        conn: &mut PgConnection,
+        col_idxs: impl IntoIterator<Item = usize>,
+    ) -> Result<()> {
+        let trgm_q = "CREATE EXTENSION IF NOT EXISTS pg_trgm";
+        println!("INFO: Running SQL query \"{trgm_q}\";");
+        // In a savepoint, since a failed statement would abort the caller's transaction.
+        let mut savepoint = conn.begin().await?;
+        let has_trgm = match sqlx::query(trgm_q).execute(&mut *savepoint).await {
+            Ok(_) => {
+                savepoint.commit().await?;
+                true
+            }
+            Err(e) => {
+                savepoint.rollback().await?;
+                eprintln!("WARNING: Failed to enable pg_trgm, text columns won't be indexed for search: {e}");
+                false
+            }
+        };
+
+        let mut index_qs = vec![];
+        for i in col_idxs {
+            let (col, col_type) = (&self.columns[i], self.header_types[i]);
+            match col_type {
+                // The key is looked up by value as well as searched, and enforced unique.
+                _ if i == self.key_idx => {
+                    index_qs.push(format!(
+                        "CREATE UNIQUE INDEX ON {} ({})",
+                        self.table_name, col
+                    ));
+                    if col_type != RecordType::TEXT {
+                        continue;
+                    }
+                }
+                RecordType::BIGINT | RecordType::DOUBLE => {
+                    index_qs.push(format!("CREATE INDEX ON {} ({})", self.table_name, col));
+                    continue;
+                }
+                RecordType::TEXT => {}
+            }
+            if has_trgm {
+                index_qs.push(format!(
+                    "CREATE INDEX ON {} USING gin ({} gin_trgm_ops)",
+                    self.table_name, col
+                ));
+            }
+        }
+        index_qs.push(format!("ANALYZE {}", self.table_name));
+        for index_q in index_qs {
+            println!("INFO: Running SQL query \"{index_q}\";");
+            sqlx::query(&index_q).execute(&mut *conn).await?;
+        }
+        Ok(())
+    }
+
+    /// Streams the csv into `table_name` with each value normalized by the same ValueParser used
+    /// to infer the column types. Rows are numbered from 1 into id in file order, which the
+    /// server's paging relies on, next to the line they start on.
+    async fn copy_rows(&self, conn: &mut PgConnection, table_name: &str) -> Result<()> {
+        let copy_csv_q = format!(
+            "COPY {}({},{SOURCE_LINE},id) FROM STDIN WITH (FORMAT csv)",
+            table_name,
+            self.columns.join(",")
+        );
+        println!("INFO: Running SQL query \"{copy_csv_q}\";");
+        let mut copy_in = conn.copy_in_raw(&copy_csv_q).await?;
+
+        let rows = self.format_reader.rows(&self.input)?;
+        let mut buf = Vec::with_capacity(1 << 16);
+        // Rows that failed to parse were already reported during type inference.
+        for (id, row) in (1..).zip(rows.records.flatten()) {
+            for (item, record_type) in row.iter().zip(self.input_types.iter()) {
+                // Unquoted empty fields are NULL, so every value is quoted.
+                if let Some(value) = self.value_parser.normalize(item, *record_type) {
+                    buf.push(b'"');
+                    buf.extend_from_slice(value.replace('"', "\"\"").as_bytes());
+                    buf.push(b'"');
+                }
+                buf.push(b',');
+            }
+            let line = self.input.line(&row);
+            buf.extend_from_slice(format!("{line},{id}\n").as_bytes());
+            if buf.len() >= 1 << 16 {
+                copy_in.send(buf.as_slice()).await?;
+                buf.clear();
+                if let Some(progress) = &self.progress {
+                    progress.store(id, Ordering::Relaxed);
+                }
+            }
+        }
+        if !buf.is_empty() {
+            copy_in.send(buf.as_slice()).await?;
+        }
+        let num_rows = copy_in.finish().await?;
+        if let Some(progress) = &self.progress {
+            progress.store(num_rows, Ordering::Relaxed);
+        }
+        println!("INFO: Copied {num_rows} rows.");
+        Ok(())
+    }
+
+    async fn write_cys_sites(&self, conn: &mut PgConnection) -> Result<()> {
+        let update_q = format!(
+            r#"
+            UPDATE {tbl} SET cys_accession = site.accession, cys_gene = site.gene, cys_residue = site.residue
+            FROM UNNEST($1::TEXT[], $2::TEXT[], $3::TEXT[], $4::INTEGER[]) AS site(id, accession, gene, residue)
+            WHERE {tbl}.{id_col}::TEXT = site.id
+            "#,
+            tbl = self.table_name,
+            id_col = self.columns[self.site_idx],
+        );
+        println!("INFO: Running SQL query \"{}\";", update_q.trim());
+        for chunk in self.cys_sites.chunks(10_000) {
+            let (ids, sites): (Vec<_>, Vec<_>) = chunk.iter().cloned().unzip();
+            let (accessions, genes, residues): (Vec<_>, Vec<_>, Vec<_>) = sites
+                .into_iter()
+                .map(|site| (site.accession, site.gene, site.residue))
+                .collect();
+            sqlx::query(&update_q)
+                .bind(ids)
+                .bind(accessions)
+                .bind(genes)
+                .bind(residues)
+                .execute(&mut *conn)
+                .await?;
+        }
+
+        for index_cols in ["cys_accession, cys_residue", "cys_gene, cys_residue"] {
+            let index_q = format!("CREATE INDEX ON {} ({})", self.table_name, index_cols);
+            println!("INFO: Running SQL query \"{index_q}\";");
+            sqlx::query(&index_q).execute(&mut *conn).await?;
+        }
+        Ok(())
+    }
+}
+
@@ -0,0 +1,57 @@
+use crate::{CSVProcessor, RecordType, ValidateForSQL, is_reserved};
+use crate::{Error, Result};
+
+impl CSVProcessor {
+    /// Replaces the inferred column names and types, e.g. with the ones chosen on screenmap's
+    /// upload page. Values that don't fit a narrower type fail the import.
+    pub fn override_columns(&mut self, columns: &[(String, RecordType)]) -> Result<()> {
+        if self.aggregations.is_some() {
+            return Err(Error::RolledUp);
+        } else if columns.len() != self.columns.len() {
+            return Err(Error::ColumnCount {
+                expected: self.columns.len(),
+                got: columns.len(),
+            });
+        }
+        let mut names: Vec<String> = vec![];
+        for (name, _) in columns.iter() {
+            if is_reserved(name) {
+                return Err(Error::InvalidColumn {
+                    column: name.clone(),
+                    reason: "it is reserved for a column every screen has",
+                });
+            }
+            let name = name.trim().validate();
+            if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+                return Err(Error::InvalidColumn {
+                    column: name,
+                    reason: "it is empty or starts with a digit",
+                });
+            } else if names.iter().any(|other| other.eq_ignore_ascii_case(&name)) {
+                return Err(Error::InvalidColumn {
+                    column: name,
+                    reason: "it is used twice",
+                });
+            }
+            names.push(name);
+        }
+        self.columns = names;
+        self.header_types = columns
+            .iter()
+            .map(|(_, record_type)| *record_type)
+            .collect();
+        self.input_types = self.header_types.clone();
+        Ok(())
+    }
+
+    /// The first `n` rows as they are in the file.
+    pub fn sample_rows(&self, n: usize) -> Result<Vec<Vec<String>>> {
+        let rows = self.format_reader.rows(&self.input)?;
+        Ok(rows
+            .records
+            .flatten()
+            .take(n)
+            .map(|row| row.iter().map(str::to_string).collect())
+            .collect())
+    }
+}
@@ -0,0 +1,225 @@
+use crate::formats::Role;
+use crate::{CSVProcessor, CYS_COLUMNS, RecordType, SOURCE_LINE};
+use crate::{Error, Result};
+use sqlx::PgConnection;
+use std::fmt::Display;
+
+/// Column holding how many peptides were rolled up into a site.
+pub const NUM_PEPTIDES: &str = "num_peptides";
+const STAGING_TABLE: &str = "rollup_staging";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aggregation {
+    Median,
+    Mean,
+    Count,
+    Min,
+    Max,
+    // the first value in file order
+    First,
+}
+
+impl Aggregation {
+    pub fn from_arg(arg: &str) -> Option<Self> {
+        match arg {
+            "median" => Some(Aggregation::Median),
+            "mean" => Some(Aggregation::Mean),
+            "count" => Some(Aggregation::Count),
+            "min" => Some(Aggregation::Min),
+            "max" => Some(Aggregation::Max),
+            "first" => Some(Aggregation::First),
+            _ => None,
+        }
+    }
+
+    /// The type of the aggregate of a column of `record_type`, or None if it can't be taken.
+    fn result_type(&self, record_type: RecordType) -> Option<RecordType> {
+        match self {
+            Aggregation::Median | Aggregation::Mean if record_type == RecordType::TEXT => None,
+            Aggregation::Median | Aggregation::Mean => Some(RecordType::DOUBLE),
+            Aggregation::Count => Some(RecordType::BIGINT),
+            Aggregation::Min | Aggregation::Max | Aggregation::First => Some(record_type),
+        }
+    }
+
+    fn sql(&self, col: &str) -> String {
+        match self {
+            Aggregation::Median => {
+                format!("percentile_cont(0.5) WITHIN GROUP (ORDER BY {col}::DOUBLE PRECISION)")
+            }
+            Aggregation::Mean => format!("avg({col})::DOUBLE PRECISION"),
+            Aggregation::Count => format!("count({col})"),
+            Aggregation::Min => format!("min({col})"),
+            Aggregation::Max => format!("max({col})"),
+            Aggregation::First => format!(
+                "(array_agg({col} ORDER BY {SOURCE_LINE}) FILTER (WHERE {col} IS NOT NULL))[1]"
+            ),
+        }
+    }
+}
+
+impl Display for Aggregation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Aggregation::Median => f.write_str("median"),
+            Aggregation::Mean => f.write_str("mean"),
+            Aggregation::Count => f.write_str("count"),
+            Aggregation::Min => f.write_str("min"),
+            Aggregation::Max => f.write_str("max"),
+            Aggregation::First => f.write_str("first"),
+        }
+    }
+}
+
+/// How rows are collapsed to one per cysteine site: numeric columns by `numeric`, text columns by
+/// their first value, unless the column is named in `per_column`.
+#[derive(Debug, Clone)]
+pub struct Rollup {
+    pub numeric: Aggregation,
+    pub per_column: Vec<(String, Aggregation)>,
+}
+
+impl Default for Rollup {
+    fn default() -> Self {
+        Self {
+            numeric: Aggregation::Median,
+            per_column: vec![],
+        }
+    }
+}
+
+impl Rollup {
+    /// Parses an --agg argument, either `<aggregation>` for every numeric column or
+    /// `<column>=<aggregation>`.
+    pub fn parse_arg(&mut self, arg: &str) -> Option<()> {
+        match arg.rsplit_once('=') {
+            Some((col, aggregation)) => {
+                let aggregation = Aggregation::from_arg(aggregation)?;
+                self.per_column.push((col.to_string(), aggregation));
+            }
+            None => self.numeric = Aggregation::from_arg(arg)?,
+        }
+        Some(())
+    }
+
+    /// The aggregation of every column and the type it results in. The site column is grouped
+    /// by, so it keeps its type.
+    pub fn resolve(
+        &self,
+        headers: &[String],
+        columns: &[String],
+        types: &[RecordType],
+        site_idx: usize,
+    ) -> Result<Vec<(Option<Aggregation>, RecordType)>> {
+        for (col, _) in self.per_column.iter() {
+            let known = headers
+                .iter()
+                .chain(columns.iter())
+                .any(|other| other.eq_ignore_ascii_case(col));
+            if !known {
+                return Err(Error::UnknownAggColumn(col.clone()));
+            }
+        }
+        let mut resolved = vec![];
+        for (i, ((header, col), record_type)) in headers.iter().zip(columns).zip(types).enumerate()
+        {
+            if i == site_idx {
+                resolved.push((None, *record_type));
+                continue;
+            }
+            let configured = self
+                .per_column
+                .iter()
+                .rfind(|(name, _)| {
+                    name.eq_ignore_ascii_case(header) || name.eq_ignore_ascii_case(col)
+                })
+                .map(|(_, aggregation)| *aggregation);
+            let aggregation = configured.unwrap_or(match record_type {
+                RecordType::TEXT => Aggregation::First,
+                _ => self.numeric,
+            });
+            let Some(result_type) = aggregation.result_type(*record_type) else {
+                return Err(Error::Aggregation {
+                    aggregation,
+                    column: header.clone(),
+                });
+            };
+            resolved.push((Some(aggregation), result_type));
+        }
+        Ok(resolved)
+    }
+}
+
+impl CSVProcessor {
+    /// Loads the rows into a staging table and creates the screen from it with one row per site,
+    /// numbered in the order each site first appears in the file. Rows without a site are left
+    /// out.
+    pub async fn write_rollup(
+        &self,
+        conn: &mut PgConnection,
+        aggregations: &[Option<Aggregation>],
+    ) -> Result<()> {
+        // The staging table is temporary and only there for the caller's transaction, so a
+        // failed rollup doesn't leave it behind on a pooled connection.
+        let staging_cols: Vec<_> = self
+            .columns
+            .iter()
+            .zip(self.input_types.iter())
+            .map(|(col, col_type)| format!("{col} {col_type}"))
+            .chain([format!("{SOURCE_LINE} BIGINT"), "id INTEGER".to_string()])
+            .collect();
+        let create_staging_q = format!(
+            "CREATE TEMP TABLE {STAGING_TABLE}({}) ON COMMIT DROP",
+            staging_cols.join(",")
+        );
+        println!("INFO: Running SQL query \"{create_staging_q}\";");
+        sqlx::query(&create_staging_q).execute(&mut *conn).await?;
+        self.copy_rows(conn, STAGING_TABLE).await?;
+
+        let site = &self.columns[self.site_idx];
+        let mut selects = vec![];
+        for (col, aggregation) in self.columns.iter().zip(aggregations) {
+            match aggregation {
+                Some(aggregation) => selects.push(format!("{} AS {col}", aggregation.sql(col))),
+                None => selects.push(col.clone()),
+            }
+        }
+        // Peptide and PSM tables count distinct peptides, other tables their rows.
+        let peptide = self
+            .roles
+            .iter()
+            .position(|role| *role == Some(Role::Peptide))
+            .map(|i| &self.columns[i]);
+        selects.push(match peptide {
+            Some(peptide) => format!("count(DISTINCT {peptide}) AS {NUM_PEPTIDES}"),
+            None => format!("count(*) AS {NUM_PEPTIDES}"),
+        });
+        selects.push(format!("min({SOURCE_LINE}) AS {SOURCE_LINE}"));
+        for (col, col_type) in CYS_COLUMNS {
+            selects.push(format!("NULL::{col_type} AS {col}"));
+        }
+        selects.push(format!(
+            "(row_number() OVER (ORDER BY min({SOURCE_LINE})))::INTEGER AS id"
+        ));
+        let rollup_q = format!(
+            "CREATE TABLE {} AS SELECT {} FROM {STAGING_TABLE} WHERE {site} IS NOT NULL GROUP BY {site}",
+            self.table_name,
+            selects.join(", "),
+        );
+        println!("INFO: Running SQL query \"{rollup_q}\";");
+        let num_sites = sqlx::query(&rollup_q)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+        let (num_rows, without_site): (i64, i64) = sqlx::query_as(&format!(
+            "SELECT count(*), count(*) FILTER (WHERE {site} IS NULL) FROM {STAGING_TABLE}"
+        ))
+        .fetch_one(&mut *conn)
+        .await?;
+        println!("INFO: Rolled {num_rows} rows up into {num_sites} sites.");
+        if without_site > 0 {
+            eprintln!("WARNING: Left out {without_site} rows without a cysteine site.");
+        }
+        Ok(())
+    }
+}
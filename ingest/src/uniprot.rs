@@ -0,0 +1,216 @@
+use crate::formats::Sequences;
+use crate::{Error, Result};
+use sqlx::PgPool;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+#[derive(Debug, Default, Clone)]
+struct Protein {
+    accession: String,
+    entry_name: Option<String>,
+    protein_name: Option<String>,
+    gene_name: Option<String>,
+    organism: Option<String>,
+    sequence: Option<String>,
+}
+
+/// Parses a UniProt FASTA header such as
+/// `sp|P04637|P53_HUMAN Cellular tumor antigen p53 OS=Homo sapiens OX=9606 GN=TP53 PE=1 SV=4`.
+fn parse_header(header: &str) -> Protein {
+    let (id, description) = header.split_once(' ').unwrap_or((header, ""));
+    let mut id_parts = id.split('|');
+    let (accession, entry_name) = match (id_parts.next(), id_parts.next(), id_parts.next()) {
+        (Some(_db), Some(accession), entry_name) => (accession, entry_name),
+        _ => (id, None),
+    };
+
+    // OS=, OX=, GN=, PE= and SV= follow the protein name, in that order when present.
+    let field_start = |key: &str| description.find(&format!(" {key}=")).map(|i| i + 1);
+    let field = |key: &str| {
+        let start = field_start(key)? + key.len() + 1;
+        let end = ["OS", "OX", "GN", "PE", "SV"]
+            .iter()
+            .filter_map(|other| field_start(other))
+            .filter(|&i| i > start)
+            .min()
+            .map_or(description.len(), |i| i - 1);
+        Some(description[start..end].trim().to_string())
+    };
+    let name_end = ["OS", "OX", "GN", "PE", "SV"]
+        .iter()
+        .filter_map(|key| field_start(key))
+        .min()
+        .map_or(description.len(), |i| i - 1);
+    let protein_name = Some(description[..name_end].trim().to_string()).filter(|s| !s.is_empty());
+
+    Protein {
+        accession: accession.to_string(),
+        entry_name: entry_name.map(str::to_string),
+        protein_name,
+        gene_name: field("GN"),
+        organism: field("OS"),
+        sequence: None,
+    }
+}
+
+fn read_fasta(fpath: &Path) -> Result<Vec<Protein>> {
+    let reader = BufReader::new(File::open(fpath).map_err(|source| Error::Read {
+        path: format!("fasta {}", fpath.display()),
+        source,
+    })?);
+    let mut proteins = vec![];
+    let mut cur: Option<(Protein, String)> = None;
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim_end();
+        if let Some(header) = line.strip_prefix('>') {
+            if let Some((mut protein, sequence)) = cur.take() {
+                protein.sequence = Some(sequence);
+                proteins.push(protein);
+            }
+            cur = Some((parse_header(header), String::new()));
+        } else if let Some((_, sequence)) = cur.as_mut() {
+            sequence.push_str(line.trim());
+        } else if !line.is_empty() {
+            return Err(Error::Layout(format!(
+                "{fpath:?} does not start with a fasta header."
+            )));
+        }
+    }
+    if let Some((mut protein, sequence)) = cur {
+        protein.sequence = Some(sequence);
+        proteins.push(protein);
+    }
+    Ok(proteins)
+}
+
+/// Reads a tab separated UniProt export. Columns are matched by their UniProt header names; only
+/// `Entry` is required.
+fn read_annotations(fpath: &Path) -> Result<Vec<Protein>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(b'\t')
+        .flexible(true)
+        .from_reader(File::open(fpath).map_err(|source| Error::Read {
+            path: format!("annotations {}", fpath.display()),
+            source,
+        })?);
+    let headers = reader.headers()?.clone();
+    let find = |names: &[&str]| {
+        headers
+            .iter()
+            .position(|h| names.iter().any(|name| h.eq_ignore_ascii_case(name)))
+    };
+    let accession_idx = find(&["Entry", "Accession"]).ok_or(Error::Layout(
+        "Annotation file has no 'Entry' column.".to_string(),
+    ))?;
+    let entry_name_idx = find(&["Entry Name", "Entry name"]);
+    let gene_idx = find(&["Gene Names (primary)", "Gene Names", "Gene names"]);
+    let protein_idx = find(&["Protein names", "Protein name"]);
+    let organism_idx = find(&["Organism"]);
+
+    let mut proteins = vec![];
+    for (i, maybe_row) in reader.records().enumerate() {
+        let row = match maybe_row {
+            Ok(row) => row,
+            Err(e) => {
+                eprintln!(
+                    "WARNING: Error parsing annotations on line {}: {e:?}",
+                    i + 2
+                );
+                continue;
+            }
+        };
+        let get = |idx: Option<usize>| {
+            idx.and_then(|idx| row.get(idx))
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+        };
+        let Some(accession) = get(Some(accession_idx)) else {
+            continue;
+        };
+        proteins.push(Protein {
+            accession,
+            entry_name: get(entry_name_idx),
+            // "Gene Names" lists the primary name first, followed by synonyms.
+            gene_name: get(gene_idx)
+                .and_then(|genes| genes.split([' ', ';']).next().map(str::to_string)),
+            protein_name: get(protein_idx),
+            organism: get(organism_idx),
+            sequence: None,
+        });
+    }
+    Ok(proteins)
+}
+
+async fn upsert(pool: &PgPool, proteins: &[Protein]) -> Result<()> {
+    // Annotation values win over the fasta header, but a missing value never clears one.
+    let upsert_q = r#"
+        INSERT INTO screenmap.proteins (accession, entry_name, protein_name, gene_name, organism, sequence)
+        SELECT * FROM UNNEST($1::TEXT[], $2::TEXT[], $3::TEXT[], $4::TEXT[], $5::TEXT[], $6::TEXT[])
+        ON CONFLICT (accession) DO UPDATE SET
+            entry_name = COALESCE(EXCLUDED.entry_name, proteins.entry_name),
+            protein_name = COALESCE(EXCLUDED.protein_name, proteins.protein_name),
+            gene_name = COALESCE(EXCLUDED.gene_name, proteins.gene_name),
+            organism = COALESCE(EXCLUDED.organism, proteins.organism),
+            sequence = COALESCE(EXCLUDED.sequence, proteins.sequence)
+    "#;
+    for chunk in proteins.chunks(5_000) {
+        let mut columns: [Vec<Option<String>>; 5] = Default::default();
+        let mut accessions = vec![];
+        for protein in chunk.iter().cloned() {
+            accessions.push(protein.accession);
+            columns[0].push(protein.entry_name);
+            columns[1].push(protein.protein_name);
+            columns[2].push(protein.gene_name);
+            columns[3].push(protein.organism);
+            columns[4].push(protein.sequence);
+        }
+        let [entry_names, protein_names, gene_names, organisms, sequences] = columns;
+        sqlx::query(upsert_q)
+            .bind(accessions)
+            .bind(entry_names)
+            .bind(protein_names)
+            .bind(gene_names)
+            .bind(organisms)
+            .bind(sequences)
+            .execute(pool)
+            .await?;
+    }
+    Ok(())
+}
+
+/// The loaded sequences by accession.
+pub async fn sequences(pool: &PgPool) -> Result<Sequences> {
+    let rows: Vec<(String, String)> = sqlx::query_as(
+        "SELECT accession, sequence FROM screenmap.proteins WHERE sequence IS NOT NULL",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().collect())
+}
+
+pub async fn import(pool: &PgPool, fasta: &Path, annotations: Option<&Path>) -> Result<()> {
+    let proteins = read_fasta(fasta)?;
+    println!("INFO: Read {} sequences from {:?}.", proteins.len(), fasta);
+    upsert(pool, &proteins).await?;
+
+    if let Some(annotations) = annotations {
+        let annotated = read_annotations(annotations)?;
+        let sequences: HashSet<_> = proteins.iter().map(|p| p.accession.as_str()).collect();
+        let missing = annotated
+            .iter()
+            .filter(|p| !sequences.contains(p.accession.as_str()))
+            .count();
+        println!(
+            "INFO: Read {} annotations from {:?} ({} without a sequence in the fasta).",
+            annotated.len(),
+            annotations,
+            missing
+        );
+        upsert(pool, &annotated).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,108 @@
+use crate::RecordType;
+use std::borrow::Cow;
+
+/// Tokens read as a missing value unless --null is passed. Empty fields are always missing.
+pub const DEFAULT_NULL_TOKENS: &[&str] = &["NA", "N/A", "#N/A", "#DIV/0!", "#VALUE!", "-", "n.d."];
+
+/// What to do with NaN, Inf and -Inf in numeric columns, in any spelling rust's f64 parser
+/// accepts. Text columns keep them as they are, e.g. a gene annotated "NaN".
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NonFinite {
+    // Load them as NULL so they don't break heatmap bounds.
+    Null,
+    // Load them as postgres' NaN, Infinity and -Infinity.
+    Keep,
+}
+
+impl NonFinite {
+    pub fn from_arg(arg: &str) -> Option<Self> {
+        match arg {
+            "null" => Some(NonFinite::Null),
+            "keep" => Some(NonFinite::Keep),
+            _ => None,
+        }
+    }
+}
+
+/// Decides how a raw csv field is typed and loaded. Type inference and the load both go through
+/// this, so a value that inferred as missing is always loaded as NULL.
+#[derive(Debug, Clone)]
+pub struct ValueParser {
+    null_tokens: Vec<String>,
+    non_finite: NonFinite,
+    // '.' or ','. Numbers are always loaded with '.'.
+    decimal: char,
+}
+
+impl ValueParser {
+    pub fn new(null_tokens: &[String], non_finite: NonFinite, decimal: char) -> Self {
+        let null_tokens = if null_tokens.is_empty() {
+            DEFAULT_NULL_TOKENS.iter().map(|t| t.to_string()).collect()
+        } else {
+            null_tokens.to_vec()
+        };
+        Self {
+            null_tokens,
+            non_finite,
+            decimal,
+        }
+    }
+
+    /// Whether `item` is missing, whatever the type of its column.
+    pub fn is_null(&self, item: &str) -> bool {
+        let item = item.trim();
+        item.is_empty()
+            || self
+                .null_tokens
+                .iter()
+                .any(|token| token.eq_ignore_ascii_case(item))
+    }
+
+    /// `item` as postgres would parse it if it is a number.
+    fn number_text<'a>(&self, item: &'a str) -> Cow<'a, str> {
+        let item = item.trim();
+        if self.decimal != '.' && item.contains(self.decimal) {
+            Cow::Owned(item.replacen(self.decimal, ".", 1))
+        } else {
+            Cow::Borrowed(item)
+        }
+    }
+
+    /// The narrowest type that holds `item`, or None if it is missing.
+    pub fn record_type(&self, item: &str) -> Option<RecordType> {
+        if self.is_null(item) {
+            return None;
+        }
+        let item = self.number_text(item);
+        Some(if item.parse::<i64>().is_ok() {
+            RecordType::BIGINT
+        } else if item.parse::<f64>().is_ok() {
+            RecordType::DOUBLE
+        } else {
+            RecordType::TEXT
+        })
+    }
+
+    /// The text to load for `item` into a column of `record_type`, or None for NULL.
+    pub fn normalize<'a>(&self, item: &'a str, record_type: RecordType) -> Option<Cow<'a, str>> {
+        if self.is_null(item) {
+            return None;
+        } else if record_type == RecordType::TEXT {
+            return Some(Cow::Borrowed(item));
+        }
+        let item = self.number_text(item);
+        if self.non_finite == NonFinite::Null && is_non_finite(&item) {
+            return None;
+        }
+        Some(match item.parse::<f64>() {
+            Ok(x) if x.is_nan() => Cow::Borrowed("NaN"),
+            Ok(x) if x == f64::INFINITY => Cow::Borrowed("Infinity"),
+            Ok(x) if x == f64::NEG_INFINITY => Cow::Borrowed("-Infinity"),
+            _ => item,
+        })
+    }
+}
+
+fn is_non_finite(item: &str) -> bool {
+    item.parse::<f64>().is_ok_and(|x| !x.is_finite())
+}
@@ -1,236 +1,500 @@
-#![feature(path_file_prefix)]
-use anyhow::{Error, Result};
+mod watch;
+
+use encoding_rs::Encoding;
+use ingest::audit::{self, Actor};
+use ingest::cys_id::CysIdParser;
+use ingest::formats::{Format, FormatReader};
+use ingest::input::{self, Input};
+use ingest::rollup::Rollup;
+use ingest::values::{NonFinite, ValueParser};
+use ingest::{CSVProcessor, Error, ValidateForSQL, catalog, ensure_schema, table_name, uniprot};
 use sqlx::PgPool;
-use std::fmt::Display;
-use std::fs::{File, canonicalize};
-use std::path::PathBuf;
+use std::fs::canonicalize;
+use std::path::{Path, PathBuf};
 use std::{env, process::exit};
 
-#[derive(Clone, Copy, PartialEq, PartialOrd)]
-enum RecordType {
-    // rank by MAX - MIN
-    BIGINT = 0,
-    DOUBLE = 1,
-    TEXT = 2,
-}
-
-impl Display for RecordType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            RecordType::BIGINT => f.write_str("BIGINT"),
-            RecordType::DOUBLE => f.write_str("DOUBLE PRECISION"),
-            RecordType::TEXT => f.write_str("TEXT"),
-        }
-    }
-}
-
 #[derive(Debug)]
 struct Args {
-    pub fpath: PathBuf,
+    pub fpaths: Vec<PathBuf>,
     pub force: bool,
+    pub cys_patterns: Vec<String>,
+    pub null_tokens: Vec<String>,
+    pub non_finite: NonFinite,
+    pub encoding: Option<&'static Encoding>,
+    pub delimiter: Option<u8>,
+    pub decimal: Option<char>,
+    pub table: Option<String>,
+    pub allow_breaking: bool,
+    pub no_skip: bool,
+    // existing table to add the csv's columns to, joined on `on`
+    pub append: Option<String>,
+    pub on: Option<String>,
+    pub key: Option<String>,
+    pub skip_lines: usize,
+    pub header_rows: usize,
+    // None to detect it from the file name
+    pub format: Option<Format>,
+    // collapse rows to one per cysteine site
+    pub rollup: Option<Rollup>,
+    // project to put imported screens in, so only its members can see them
+    pub project: Option<String>,
 }
 
 impl Args {
     fn new(args_raw: Vec<String>) -> Args {
         let mut args = Args {
-            fpath: PathBuf::new(),
+            fpaths: vec![],
             force: false,
+            cys_patterns: vec![],
+            null_tokens: vec![],
+            non_finite: NonFinite::Null,
+            encoding: None,
+            delimiter: None,
+            decimal: None,
+            table: None,
+            allow_breaking: false,
+            no_skip: false,
+            append: None,
+            on: None,
+            key: None,
+            skip_lines: 0,
+            header_rows: 1,
+            format: None,
+            rollup: None,
+            project: None,
         };
-        let mut fpath = None;
-        for arg in args_raw.iter().skip(1) {
+        let mut aggs = vec![];
+        let mut args_iter = args_raw.iter().skip(1);
+        while let Some(arg) = args_iter.next() {
             if arg == "-f" {
                 if args.force {
                     eprintln!("ERROR: Only pass -f once.");
                 } else {
                     args.force = true;
                 }
-            } else if fpath.is_some() {
-                eprintln!("ERROR: Extraneous argument {:?} found.", arg);
-                exit(1);
+            } else if arg == "--allow-breaking" {
+                args.allow_breaking = true;
+            } else if arg == "--no-skip" {
+                args.no_skip = true;
+            } else if arg == "--cys-pattern" {
+                let Some(pattern) = args_iter.next() else {
+                    eprintln!("ERROR: --cys-pattern expects a regex.");
+                    exit(1);
+                };
+                args.cys_patterns.push(pattern.clone());
+            } else if arg == "--null" {
+                let Some(token) = args_iter.next() else {
+                    eprintln!("ERROR: --null expects a token.");
+                    exit(1);
+                };
+                args.null_tokens.push(token.clone());
+            } else if arg == "--non-finite" {
+                let Some(non_finite) = args_iter.next().and_then(|arg| NonFinite::from_arg(arg))
+                else {
+                    eprintln!("ERROR: --non-finite expects null or keep.");
+                    exit(1);
+                };
+                args.non_finite = non_finite;
+            } else if arg == "--encoding" {
+                let Some(label) = args_iter.next() else {
+                    eprintln!("ERROR: --encoding expects auto or an encoding label.");
+                    exit(1);
+                };
+                args.encoding = (label != "auto").then(|| {
+                    Encoding::for_label(label.as_bytes()).unwrap_or_else(|| {
+                        eprintln!("ERROR: Unknown encoding {label:?}.");
+                        exit(1);
+                    })
+                });
+            } else if arg == "--delimiter" {
+                let Some(delimiter) = args_iter.next() else {
+                    eprintln!("ERROR: --delimiter expects auto or a single character.");
+                    exit(1);
+                };
+                args.delimiter = (delimiter != "auto").then(|| {
+                    input::parse_delimiter(delimiter).unwrap_or_else(|| {
+                        eprintln!("ERROR: Invalid delimiter {delimiter:?}.");
+                        exit(1);
+                    })
+                });
+            } else if arg == "--decimal" {
+                args.decimal = match args_iter.next().map(String::as_str) {
+                    Some("auto") => None,
+                    Some(".") => Some('.'),
+                    Some(",") => Some(','),
+                    _ => {
+                        eprintln!("ERROR: --decimal expects auto, . or ,");
+                        exit(1);
+                    }
+                };
+            } else if arg == "--table" {
+                let Some(table) = args_iter.next() else {
+                    eprintln!("ERROR: --table expects a table name.");
+                    exit(1);
+                };
+                args.table = Some(table.clone());
+            } else if arg == "--append" {
+                let Some(table) = args_iter.next() else {
+                    eprintln!("ERROR: --append expects a table name.");
+                    exit(1);
+                };
+                args.append = Some(table.clone());
+            } else if arg == "--on" {
+                let Some(key) = args_iter.next() else {
+                    eprintln!("ERROR: --on expects a column name.");
+                    exit(1);
+                };
+                args.on = Some(key.clone());
+            } else if arg == "--key" {
+                let Some(key) = args_iter.next() else {
+                    eprintln!("ERROR: --key expects a column name.");
+                    exit(1);
+                };
+                args.key = Some(key.clone());
+            } else if arg == "--skip-lines" {
+                let Some(skip_lines) = args_iter.next().and_then(|arg| arg.parse().ok()) else {
+                    eprintln!("ERROR: --skip-lines expects a number of lines.");
+                    exit(1);
+                };
+                args.skip_lines = skip_lines;
+            } else if arg == "--header-rows" {
+                let Some(header_rows) = args_iter
+                    .next()
+                    .and_then(|arg| arg.parse().ok())
+                    .filter(|rows| *rows > 0)
+                else {
+                    eprintln!("ERROR: --header-rows expects a number of rows, at least 1.");
+                    exit(1);
+                };
+                args.header_rows = header_rows;
+            } else if arg == "--format" {
+                args.format = match args_iter.next().map(String::as_str) {
+                    Some("auto") => None,
+                    Some(format) if Format::from_arg(format).is_some() => Format::from_arg(format),
+                    _ => {
+                        eprintln!("ERROR: --format expects auto, csv, maxquant, fragpipe or mztab.");
+                        exit(1);
+                    }
+                };
+            } else if arg == "--project" {
+                let Some(project) = args_iter.next() else {
+                    eprintln!("ERROR: --project expects a project name.");
+                    exit(1);
+                };
+                args.project = Some(project.clone());
+            } else if arg == "--rollup" {
+                args.rollup = Some(Rollup::default());
+            } else if arg == "--agg" {
+                let Some(agg) = args_iter.next() else {
+                    eprintln!("ERROR: --agg expects [<column>=]median|mean|count|min|max|first.");
+                    exit(1);
+                };
+                aggs.push(agg.clone());
+            } else if arg == "-" {
+                args.fpaths.push(PathBuf::from(arg));
             } else {
-                let err_msg = "ERROR: Failed to canonicalize path.";
-                fpath = Some(canonicalize(PathBuf::from(&arg)).expect(err_msg));
+                let err_msg = format!("ERROR: Failed to canonicalize path {arg:?}.");
+                args.fpaths.push(canonicalize(PathBuf::from(&arg)).expect(&err_msg));
             }
         }
-        if args_raw.len() < 2 {
-            eprintln!("ERROR: Too few arguments supplied. Supply the input file as argument 1.");
+        if let Some(rollup) = args.rollup.as_mut() {
+            for agg in aggs.iter() {
+                if rollup.parse_arg(agg).is_none() {
+                    eprintln!("ERROR: --agg expects [<column>=]median|mean|count|min|max|first, got {agg:?}.");
+                    exit(1);
+                }
+            }
+        } else if !aggs.is_empty() {
+            eprintln!("ERROR: --agg can only be passed with --rollup.");
+            exit(1);
+        }
+        if args.fpaths.is_empty() {
+            eprintln!("ERROR: Too few arguments supplied. Supply the input files as arguments.");
+            exit(1);
+        } else if args.table.is_some() && args.fpaths.len() > 1 {
+            eprintln!("ERROR: --table can only be passed with a single input file.");
+            exit(1);
+        } else if args.append.is_some() && (args.table.is_some() || args.fpaths.len() > 1) {
+            eprintln!("ERROR: --append takes a single input file and no --table.");
+            exit(1);
+        } else if args.on.is_some() && args.append.is_none() {
+            eprintln!("ERROR: --on can only be passed with --append.");
+            exit(1);
+        } else if args.key.is_some() && args.append.is_some() {
+            eprintln!("ERROR: Pass --on instead of --key with --append.");
             exit(1);
-        } else if args_raw.len() > 3 {
-            eprintln!(
-                "ERROR: Too many arguments supplied. Only supply the input file as argument 1."
-            );
+        } else if args.rollup.is_some() && args.append.is_some() {
+            eprintln!("ERROR: --rollup can't be passed with --append.");
+            exit(1);
+        } else if args.project.is_some() && args.append.is_some() {
+            eprintln!("ERROR: --project can't be passed with --append.");
+            exit(1);
+        } else if args.table.is_none()
+            && args.append.is_none()
+            && args.fpaths.iter().any(|fpath| fpath.as_os_str() == "-")
+        {
+            eprintln!("ERROR: Pass --table <name> when reading from stdin.");
             exit(1);
         }
-        args.fpath = fpath.expect("ERROR: Supply a csv file to add to the db.");
         args
     }
 }
 
-struct CSVProcessor {
-    table_name: String,
-    header_types: Vec<RecordType>,
-    columns: Vec<String>,
-    fpath: PathBuf,
+#[derive(Debug)]
+struct UniprotArgs {
+    pub fasta: PathBuf,
+    pub annotations: Option<PathBuf>,
 }
 
-trait ValidateForSQL {
-    fn validate(&self) -> String;
-}
-impl ValidateForSQL for &str {
-    fn validate(&self) -> String {
-        if self == &"id" {
-            eprintln!("ERROR: use of reserved word 'id' in csv.")
+impl UniprotArgs {
+    fn new(args_raw: Vec<String>) -> UniprotArgs {
+        let mut paths = args_raw.iter().skip(2).map(|arg| {
+            let err_msg = format!("ERROR: Failed to canonicalize path {arg:?}.");
+            canonicalize(PathBuf::from(arg)).expect(&err_msg)
+        });
+        let Some(fasta) = paths.next() else {
+            eprintln!("ERROR: Usage: process_csv uniprot <fasta> [annotations.tsv]");
+            exit(1);
+        };
+        let annotations = paths.next();
+        if paths.next().is_some() {
+            eprintln!("ERROR: Too many arguments supplied to uniprot.");
+            exit(1);
         }
-        self.replace(" ", "_")
-            .replace("-", "_")
-            .replace("(", "_")
-            .replace(")", "_")
-            .replace(".", "_")
+        UniprotArgs { fasta, annotations }
     }
 }
 
-impl CSVProcessor {
-    fn new(fpath: PathBuf) -> Result<Self> {
-        let err_msg = format!("Failed to read file {:?} passed on command line.", fpath);
-        let mut reader = csv::ReaderBuilder::new().from_reader(File::open(&fpath).expect(&err_msg));
-        let err_msg = "Failed to parse input headers";
-        let headers = reader
-            .headers()
-            .expect(err_msg)
-            .into_iter()
-            .map(str::to_string)
-            .collect::<Vec<_>>();
-        let num_headers = headers.len();
-        let mut header_types = vec![None; num_headers];
-        for (i, maybe_row) in reader.records().enumerate() {
-            if let Err(e) = maybe_row {
-                eprintln!("WARNING: Error parsing csv on line {}: {:?}", i + 1, e);
-            } else if let Ok(row) = maybe_row {
-                for (maybe_header_type, item) in header_types.iter_mut().zip(row.iter()) {
-                    if item.is_empty() {
-                        continue;
-                    }
-                    let smallest_type = if str::parse::<i64>(item).is_ok() {
-                        RecordType::BIGINT
-                    } else if str::parse::<f64>(item).is_ok() {
-                            RecordType::DOUBLE
-                    } else {
-                        RecordType::TEXT
-                    };
-                    if let Some(header_type) = maybe_header_type && smallest_type > *header_type {
-                        *header_type = smallest_type
-                    } else {
-                        *maybe_header_type = Some(smallest_type);
-                    }
-                }
-            }
-        }
+#[derive(Debug)]
+struct WatchArgs {
+    pub dir: PathBuf,
+    // passed on to the import of each file
+    pub options: Vec<String>,
+}
 
-        let columns: Vec<_> = headers.iter().map(|s| s.as_str().validate()).collect();
-        for c in columns.iter() {
-            if c.chars().next().expect("ERROR: Empty column name.").is_digit(10) {
-                let err_msg = format!("ERROR: Invalid column name starting with digit: {}.", c);
-                eprintln!("{}", err_msg);
-            }
+impl WatchArgs {
+    fn new(args_raw: Vec<String>) -> WatchArgs {
+        let Some(dir) = args_raw.get(2) else {
+            eprintln!("ERROR: Usage: process_csv watch <dir> [options]");
+            exit(1);
+        };
+        let err_msg = format!("ERROR: Failed to canonicalize path {dir:?}.");
+        let dir = canonicalize(PathBuf::from(dir)).expect(&err_msg);
+        if !dir.is_dir() {
+            eprintln!("ERROR: {dir:?} is not a directory.");
+            exit(1);
         }
-        let header_types: Vec<_> = header_types.into_iter().map(Option::unwrap).collect();
-        println!("CSV header -> SQL column: Detected Type");
-        headers
-            .iter()
-            .zip(columns.iter())
-            .zip(header_types.iter())
-            .for_each(|((header, column), header_type)| {
-                println!("{} -> {}: {}", header, column, header_type)
-            });
-
-        let table_name = fpath
-            .file_prefix()
-            .ok_or(Error::msg("ERROR: Failed getting file prefix"))?
-            .to_str()
-            .ok_or(Error::msg("ERROR: Failed converting OsStr to string"))?
-            .validate()
-            .to_ascii_lowercase();
-        if table_name.len() > 63 {
-            eprintln!("ERROR: table name \"{table_name}\" has length greater than 63, which is the max allowed by postgrsql.");
-            panic!();
+        WatchArgs {
+            dir,
+            options: args_raw[3..].to_vec(),
         }
-        Ok(Self {
-            table_name,
-            columns,
-            header_types,
-            fpath,
-        })
     }
+}
 
-    async fn write_db(&self, db_url: &str, force: bool) -> Result<()> {
-        let pool = PgPool::connect(db_url).await?;
-        println!("{}", self.table_name);
-        let table_exists = sqlx::query!(
-            "SELECT EXISTS (SELECT FROM pg_tables WHERE tablename = $1)",
-            self.table_name
-        )
-        .fetch_one(&pool)
-        .await
-        .unwrap()
-        .exists
-        .unwrap();
-        println!("{}", table_exists);
-        dbg!(&self.table_name);
-        if table_exists {
-            if force {
-                let drop_table_q = format!("DROP TABLE {}", self.table_name);
-                println!("INFO: Running SQL query \"{}\";", drop_table_q);
-                sqlx::query(&drop_table_q).execute(&pool).await?;
-            } else {
-                eprintln!(
-                    "ERROR: Table with name (derived from input file) {:?} already exists. Pass -f to delete existing table (probably a bad idea)",
-                    self.table_name
-                );
-            }
-        }
-
-        let mut create_tbl_q = self.columns.iter().zip(self.header_types.iter()).fold(
-            format!("CREATE TABLE {}(", self.table_name),
-            |acc, (header, header_type)| format!("{}{} {},", acc, header, header_type),
-        );
-        create_tbl_q.pop();
-        create_tbl_q.push(')');
-        println!("INFO: Running SQL query \"{}\";", create_tbl_q);
-        sqlx::query(&create_tbl_q).execute(&pool).await?;
-
-        let mut copy_csv_q = self
-            .columns
-            .iter()
-            .fold(format!("COPY {}(", self.table_name), |acc, header| {
-                format!("{}{},", acc, header)
-            });
-        copy_csv_q.pop();
-        let fpath_str = self.fpath.to_str().unwrap();
-        copy_csv_q = format!(
-            "{}) FROM '{}' DELIMITER ',' CSV HEADER",
-            copy_csv_q, fpath_str
-        );
-        println!("INFO: Running SQL query \"{}\";", copy_csv_q);
-        sqlx::query(&copy_csv_q).execute(&pool).await?;
-
-        let add_id_q = format!(
-            "ALTER TABLE {} ADD COLUMN id SERIAL PRIMARY KEY",
-            self.table_name
-        );
-        println!("INFO: Running SQL query \"{}\";", add_id_q);
-        sqlx::query(&add_id_q).execute(&pool).await?;
-
-        Ok(())
-    }
+/// The value of `result`, or else prints its error, which ingest words to be shown as it is, and
+/// exits.
+fn or_exit<T>(result: ingest::Result<T>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("{e}");
+        exit(1);
+    })
 }
 
 #[tokio::main]
 async fn main() {
-    dbg!("h;i");
-    let args = Args::new(env::args().collect());
-    dbg!(&args);
+    let args_raw: Vec<String> = env::args().collect();
     let err_msg = "ERROR: Set the DATABASE_URL environment variable";
     let db_url = env::var("DATABASE_URL").expect(err_msg);
-    let processor = CSVProcessor::new(args.fpath).unwrap();
-    processor.write_db(&db_url, args.force).await.unwrap();
+    if args_raw.get(1).is_some_and(|arg| arg == "uniprot") {
+        let args = UniprotArgs::new(args_raw);
+        let pool = or_exit(PgPool::connect(&db_url).await.map_err(Error::from));
+        or_exit(ensure_schema(&pool).await);
+        or_exit(uniprot::import(&pool, &args.fasta, args.annotations.as_deref()).await);
+        return;
+    }
+    if args_raw.get(1).is_some_and(|arg| arg == "watch") {
+        let args = WatchArgs::new(args_raw);
+        watch::run(&args.dir, &args.options).await.unwrap_or_else(|e| {
+            eprintln!("{e}");
+            exit(1);
+        });
+        return;
+    }
+    let args = Args::new(args_raw);
+    let cys_parser = or_exit(CysIdParser::new(&args.cys_patterns));
+    let pool = or_exit(PgPool::connect(&db_url).await.map_err(Error::from));
+    or_exit(ensure_schema(&pool).await);
+    let actor = Actor::command_line();
+    let mut summary = vec![];
+    let mut failed = false;
+    for fpath in args.fpaths.iter() {
+        match process_file(fpath, &args, &pool, &cys_parser, &actor).await {
+            Ok(processed) => summary.push(processed),
+            Err(e) => {
+                // The batch goes on; the error is printed in full here and shortened below.
+                eprintln!("{e}");
+                failed = true;
+                let source = if fpath.as_os_str() == "-" {
+                    "stdin".to_string()
+                } else {
+                    fpath.display().to_string()
+                };
+                let message = e.to_string();
+                let message = message
+                    .trim_start_matches("ERROR: ")
+                    .lines()
+                    .next()
+                    .unwrap_or_default();
+                summary.push((source, format!("failed: {message}")));
+            }
+        }
+    }
+
+    println!("INFO: Summary:");
+    for (source, outcome) in summary {
+        println!("    {source}: {outcome}");
+    }
+    if failed {
+        exit(1);
+    }
+}
+
+/// Imports `fpath`, appends it or skips it as unchanged, and returns its name and what became of
+/// it for the summary.
+async fn process_file(
+    fpath: &Path,
+    args: &Args,
+    pool: &PgPool,
+    cys_parser: &CysIdParser,
+    actor: &Actor,
+) -> ingest::Result<(String, String)> {
+    let format = args.format.unwrap_or_else(|| Format::detect(fpath));
+    // Search engines write tab separated tables.
+    let delimiter = match format {
+        Format::Csv => args.delimiter,
+        _ => args.delimiter.or(Some(b'\t')),
+    };
+    let sequences = if format.needs_sequences() {
+        uniprot::sequences(pool).await?
+    } else {
+        Default::default()
+    };
+    let format_reader = FormatReader::new(format, sequences);
+    let input = Input::open(
+        fpath.to_path_buf(),
+        args.encoding,
+        delimiter,
+        args.skip_lines,
+        args.header_rows,
+    )?;
+    let source = input.display_name();
+    if let Some(append) = args.append.as_deref() {
+        let table_name = table_name(&input, Some(append))?;
+        let checksum = catalog::checksum(input.path())?;
+        let decimal = args.decimal.map_or_else(|| input.sniff_decimal(), Ok)?;
+        let value_parser = ValueParser::new(&args.null_tokens, args.non_finite, decimal);
+        // Without --on, the csv is joined on the screen's key column.
+        let recorded = catalog::recorded_import(pool, &table_name).await?;
+        let key = args
+            .on
+            .clone()
+            .or(recorded.and_then(|recorded| recorded.key_column));
+        let processor = CSVProcessor::new(
+            input,
+            table_name,
+            key.as_deref(),
+            format_reader,
+            cys_parser,
+            value_parser,
+            None,
+        )?;
+        let mut tx = pool.begin().await?;
+        processor.append_db(&mut tx, args.force).await?;
+        let detail = format!("{source}, sha256 {checksum}");
+        audit::record(
+            &mut *tx,
+            actor,
+            "append",
+            Some(&processor.table_name),
+            Some(&detail),
+        )
+        .await?;
+        tx.commit().await?;
+        return Ok((source, format!("appended, sha256 {checksum}")));
+    }
+    let table_name = table_name(&input, args.table.as_deref())?;
+    let checksum = catalog::checksum(input.path())?;
+    let project_id = match args.project.as_deref() {
+        Some(project) => Some(catalog::project_id(pool, project).await?),
+        None => None,
+    };
+    let recorded = catalog::recorded_import(pool, &table_name).await?;
+    // A reimport keeps the key column of the last import unless --key is passed.
+    let recorded_key = recorded
+        .as_ref()
+        .and_then(|recorded| recorded.key_column.clone());
+    let key = args.key.clone().or(recorded_key.clone());
+    // The aggregations aren't recorded, so a rolled up import is always redone.
+    let unchanged = args.rollup.is_none()
+        && recorded.as_ref().is_some_and(|recorded| {
+            recorded.checksum == checksum
+                && key.as_deref().map(|key| key.validate().to_ascii_lowercase()) == recorded_key
+                && project_id.is_none_or(|project_id| recorded.project_id == Some(project_id))
+        });
+    if !args.no_skip && unchanged {
+        println!("INFO: Skipping {source}, unchanged since it was imported into {table_name:?}.");
+        return Ok((source, format!("skipped (unchanged), sha256 {checksum}")));
+    }
+
+    let decimal = args.decimal.map_or_else(|| input.sniff_decimal(), Ok)?;
+    println!(
+        "INFO: Reading {} ({:?}, {}) as {} with delimiter {:?} and decimal separator {:?}.",
+        source,
+        input.compression,
+        format,
+        input.encoding.name(),
+        input.delimiter as char,
+        decimal
+    );
+    let mut preamble = input.preamble()?;
+    let value_parser = ValueParser::new(&args.null_tokens, args.non_finite, decimal);
+    let processor = CSVProcessor::new(
+        input,
+        table_name,
+        key.as_deref(),
+        format_reader,
+        cys_parser,
+        value_parser,
+        args.rollup.as_ref(),
+    )?;
+    preamble.extend(processor.metadata.iter().cloned());
+    for line in preamble.iter() {
+        println!("INFO: Preamble: {line}");
+    }
+    let mut tx = pool.begin().await?;
+    let replaced = processor
+        .write_db(&mut tx, args.force, args.allow_breaking)
+        .await?;
+    processor.record(&mut tx, &source, &checksum, &preamble).await?;
+    let mut detail = format!("{source}, sha256 {checksum}");
+    // A reimport without --project leaves the screen where it is.
+    if let (Some(project), Some(project_id)) = (args.project.as_deref(), project_id) {
+        catalog::set_project(&mut tx, &processor.table_name, project_id).await?;
+        detail.push_str(&format!(", into {project}"));
+    }
+    let (decision, action) = if replaced {
+        ("reimported", "replace")
+    } else {
+        ("imported", "import")
+    };
+    audit::record(
+        &mut *tx,
+        actor,
+        action,
+        Some(&processor.table_name),
+        Some(&detail),
+    )
+    .await?;
+    tx.commit().await?;
+    Ok((source, format!("{decision}, sha256 {checksum}")))
 }
@@ -0,0 +1,120 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::process::Command;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+// A file counts as completely written once its size and mtime haven't changed for this long.
+const STABLE_FOR: Duration = Duration::from_secs(10);
+const DONE_DIR: &str = "done";
+const FAILED_DIR: &str = "failed";
+
+/// Imports every file that is dropped into `dir` and moves it to done/ or failed/ with the
+/// import's output next to it. Runs until killed.
+pub async fn run(dir: &Path, options: &[String]) -> Result<()> {
+    for sub_dir in [DONE_DIR, FAILED_DIR] {
+        fs::create_dir_all(dir.join(sub_dir))
+            .with_context(|| format!("ERROR: Failed to create {sub_dir}/ in {dir:?}."))?;
+    }
+    // Each file is imported by a child process, so a file that panics the importer doesn't
+    // take the watcher down with it.
+    let exe = env::current_exe()?;
+    println!("INFO: Watching {dir:?} for files to import with options {options:?}.");
+
+    let mut seen: HashMap<PathBuf, (u64, SystemTime)> = HashMap::new();
+    // Files that couldn't be imported and moved out, with the size and mtime they had then. They
+    // are still in `dir`, so they're skipped until they change rather than imported every poll.
+    let mut failed: HashMap<PathBuf, (u64, SystemTime)> = HashMap::new();
+    loop {
+        let mut current = list_files(dir)?;
+        failed.retain(|fpath, stat| current.get(fpath) == Some(stat));
+        let stable: Vec<_> = current
+            .iter()
+            .filter(|(fpath, stat)| {
+                seen.get(*fpath) == Some(*stat)
+                    && stat.1.elapsed().unwrap_or_default() >= STABLE_FOR
+                    && !failed.contains_key(*fpath)
+            })
+            .map(|(fpath, stat)| (fpath.clone(), *stat))
+            .collect();
+        for (fpath, stat) in stable {
+            current.remove(&fpath);
+            if let Err(e) = import(&exe, dir, &fpath, options).await {
+                eprintln!("ERROR: Failed to import {fpath:?}, skipping it until it changes: {e:#}");
+                failed.insert(fpath, stat);
+            }
+        }
+        seen = current;
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// The size and mtime of every file directly in `dir`, leaving out hidden files such as the
+/// logs of running imports.
+fn list_files(dir: &Path) -> Result<HashMap<PathBuf, (u64, SystemTime)>> {
+    let mut files = HashMap::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("ERROR: Failed to read {dir:?}."))? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        // The file may have been removed since the directory was read.
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.is_file() {
+            files.insert(entry.path(), (metadata.len(), metadata.modified()?));
+        }
+    }
+    Ok(files)
+}
+
+async fn import(exe: &Path, dir: &Path, fpath: &Path, options: &[String]) -> Result<()> {
+    let name = fpath
+        .file_name()
+        .map(OsStr::to_string_lossy)
+        .unwrap_or_default();
+    let log_path = dir.join(format!(".{name}.log"));
+    let log = File::create(&log_path)?;
+    println!("INFO: Importing {fpath:?}.");
+    let status = Command::new(exe)
+        .args(options)
+        .arg(fpath)
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .status()
+        .await?;
+
+    let sub_dir = if status.success() {
+        println!("INFO: Imported {fpath:?}, moving it to {DONE_DIR}/.");
+        DONE_DIR
+    } else {
+        eprintln!("WARNING: Importing {fpath:?} failed ({status}), moving it to {FAILED_DIR}/.");
+        FAILED_DIR
+    };
+    let sub_dir = dir.join(sub_dir);
+    let name = free_name(&sub_dir, &name);
+    fs::rename(fpath, sub_dir.join(&name))?;
+    fs::rename(&log_path, sub_dir.join(format!("{name}.log")))?;
+    Ok(())
+}
+
+/// `name`, or if it or its log is in `sub_dir` from an earlier file of the same name, `name` with
+/// a counter before its extensions, e.g. screen-2.csv.gz, so the earlier one is kept.
+fn free_name(sub_dir: &Path, name: &str) -> String {
+    let (stem, extensions) = match name.split_once('.') {
+        Some((stem, extensions)) => (stem, format!(".{extensions}")),
+        None => (name, String::new()),
+    };
+    (1..)
+        .map(|n| match n {
+            1 => name.to_string(),
+            n => format!("{stem}-{n}{extensions}"),
+        })
+        .find(|name| !sub_dir.join(name).exists() && !sub_dir.join(format!("{name}.log")).exists())
+        .expect("the counter runs out only after every name is taken")
+}
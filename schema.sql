@@ -0,0 +1,165 @@
+-- Tables used by screenmap itself, as opposed to the screens imported by process_csv. Both
+-- process_csv and the server run this on startup, so every statement must be idempotent.
+CREATE SCHEMA IF NOT EXISTS screenmap;
+
+-- Loaded from UniProt by `process_csv uniprot`.
+CREATE TABLE IF NOT EXISTS screenmap.proteins (
+    accession TEXT PRIMARY KEY,
+    entry_name TEXT,
+    protein_name TEXT,
+    gene_name TEXT,
+    organism TEXT,
+    sequence TEXT
+);
+CREATE INDEX IF NOT EXISTS proteins_gene_name_idx ON screenmap.proteins (gene_name);
+
+-- One row per screen imported by process_csv.
+CREATE TABLE IF NOT EXISTS screenmap.screens (
+    name TEXT PRIMARY KEY,
+    source TEXT,
+    -- sha256 of the input file as read, before decompression.
+    checksum TEXT NOT NULL,
+    imported_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+-- Column that identifies a row of the screen, enforced unique by process_csv. NULL for screens
+-- imported before it was recorded, which are keyed by their first column.
+ALTER TABLE screenmap.screens ADD COLUMN IF NOT EXISTS key_column TEXT;
+-- Metadata lines above the header of instrument exports, skipped with --skip-lines.
+ALTER TABLE screenmap.screens ADD COLUMN IF NOT EXISTS preamble TEXT[] NOT NULL DEFAULT '{}';
+-- Set on the admin page. Archived screens are left out of the search but kept, so they can be
+-- restored.
+ALTER TABLE screenmap.screens ADD COLUMN IF NOT EXISTS title TEXT;
+ALTER TABLE screenmap.screens ADD COLUMN IF NOT EXISTS description TEXT;
+ALTER TABLE screenmap.screens ADD COLUMN IF NOT EXISTS archived_at TIMESTAMPTZ;
+
+-- What the known columns of a screen hold, e.g. its key, cysteine sites, ratios or intensities.
+-- A column can have several roles.
+CREATE TABLE IF NOT EXISTS screenmap.screen_columns (
+    screen TEXT NOT NULL REFERENCES screenmap.screens (name) ON DELETE CASCADE ON UPDATE CASCADE,
+    column_name TEXT NOT NULL,
+    role TEXT NOT NULL,
+    PRIMARY KEY (screen, column_name, role)
+);
+
+-- Names the website shows for the columns of a screen instead of the column names.
+CREATE TABLE IF NOT EXISTS screenmap.column_display_names (
+    screen TEXT NOT NULL REFERENCES screenmap.screens (name) ON DELETE CASCADE ON UPDATE CASCADE,
+    column_name TEXT NOT NULL,
+    display_name TEXT NOT NULL,
+    PRIMARY KEY (screen, column_name)
+);
+
+-- Imports started from the upload page, which the server runs one at a time in the background.
+CREATE TABLE IF NOT EXISTS screenmap.jobs (
+    id BIGSERIAL PRIMARY KEY,
+    -- The stored upload, under <temp dir>/screenmap-uploads/<upload_id>/<file_name>.
+    upload_id TEXT NOT NULL,
+    file_name TEXT NOT NULL,
+    table_name TEXT NOT NULL,
+    -- The columns as the admin edited them, a json list of UploadColumn.
+    columns JSONB NOT NULL,
+    replace BOOLEAN NOT NULL DEFAULT false,
+    state TEXT NOT NULL DEFAULT 'queued'
+        CHECK (state IN ('queued', 'running', 'succeeded', 'failed')),
+    rows_processed BIGINT NOT NULL DEFAULT 0,
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    started_at TIMESTAMPTZ,
+    finished_at TIMESTAMPTZ
+);
+CREATE INDEX IF NOT EXISTS jobs_queued_idx ON screenmap.jobs (id) WHERE state = 'queued';
+-- An upload is imported by one job at a time, since the job deletes it when it finishes.
+CREATE UNIQUE INDEX IF NOT EXISTS jobs_active_upload_idx ON screenmap.jobs (upload_id)
+    WHERE state IN ('queued', 'running');
+-- Who queued the job and how, for the audit log entry of the import: the user's id and name, and
+-- 'web' or 'api'. NULL for jobs queued before it was recorded.
+ALTER TABLE screenmap.jobs ADD COLUMN IF NOT EXISTS queued_by BIGINT;
+ALTER TABLE screenmap.jobs ADD COLUMN IF NOT EXISTS queued_by_name TEXT;
+ALTER TABLE screenmap.jobs ADD COLUMN IF NOT EXISTS queued_via TEXT;
+-- The project to put the screen in, by name so deleting the project fails the job. NULL leaves a
+-- replaced screen where it is and publishes a new one.
+ALTER TABLE screenmap.jobs ADD COLUMN IF NOT EXISTS project TEXT;
+
+-- Local accounts, created with `screenmap create-admin` and `screenmap create-user`.
+CREATE TABLE IF NOT EXISTS screenmap.users (
+    id BIGSERIAL PRIMARY KEY,
+    username TEXT NOT NULL UNIQUE,
+    -- argon2 hash in PHC string format, which includes its salt and parameters.
+    password_hash TEXT NOT NULL,
+    is_admin BOOLEAN NOT NULL DEFAULT false,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Logged in browsers, by the sha256 of the token in their session cookie.
+CREATE TABLE IF NOT EXISTS screenmap.sessions (
+    token_hash TEXT PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES screenmap.users (id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+-- Groups of unpublished screens, which only the members of their project can see.
+CREATE TABLE IF NOT EXISTS screenmap.projects (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+-- NULL for published screens, which every user can see. A project can't be deleted while it
+-- still has screens, which would publish them.
+ALTER TABLE screenmap.screens ADD COLUMN IF NOT EXISTS project_id BIGINT REFERENCES screenmap.projects (id);
+
+-- Viewers see the screens of the project, editors also describe them, and admins also rename,
+-- archive and delete them and manage the members. Site admins (users.is_admin) can do all of it
+-- in every project.
+CREATE TABLE IF NOT EXISTS screenmap.project_members (
+    project_id BIGINT NOT NULL REFERENCES screenmap.projects (id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL REFERENCES screenmap.users (id) ON DELETE CASCADE,
+    role TEXT NOT NULL CHECK (role IN ('viewer', 'editor', 'admin')),
+    PRIMARY KEY (project_id, user_id)
+);
+
+-- Personal API tokens, sent as `Authorization: Bearer <token>` by scripts and notebooks. Like
+-- sessions they are stored by sha256, and they only allow what their scopes (read, write) and
+-- their user allow.
+CREATE TABLE IF NOT EXISTS screenmap.api_tokens (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES screenmap.users (id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    scopes TEXT[] NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    -- NULL for tokens that don't expire.
+    expires_at TIMESTAMPTZ,
+    last_used_at TIMESTAMPTZ
+);
+
+-- Who imported, replaced, appended to, changed, deleted or exported which screen and when, for
+-- compliance reviews. Rows are only ever inserted: the trigger below refuses the rest. user_id has
+-- no foreign key so entries outlive their user, and screen is the name at the time.
+CREATE TABLE IF NOT EXISTS screenmap.audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    -- NULL for process_csv, whose actor is the account it ran as.
+    user_id BIGINT,
+    actor TEXT NOT NULL,
+    -- 'process_csv', 'web' for browser sessions or 'api' for API tokens.
+    via TEXT NOT NULL,
+    -- e.g. 'import', 'replace', 'append', 'rename', 'archive', 'delete' or 'export', see
+    -- AuditAction in screenmap/src/interface.rs.
+    action TEXT NOT NULL,
+    screen TEXT,
+    detail TEXT
+);
+CREATE INDEX IF NOT EXISTS audit_log_at_idx ON screenmap.audit_log (at);
+CREATE INDEX IF NOT EXISTS audit_log_screen_idx ON screenmap.audit_log (screen, at);
+
+CREATE OR REPLACE FUNCTION screenmap.audit_log_append_only() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    RAISE EXCEPTION 'screenmap.audit_log is append-only';
+END;
+$$;
+CREATE OR REPLACE TRIGGER audit_log_append_only BEFORE UPDATE OR DELETE ON screenmap.audit_log
+    FOR EACH ROW EXECUTE FUNCTION screenmap.audit_log_append_only();
+CREATE OR REPLACE TRIGGER audit_log_no_truncate BEFORE TRUNCATE ON screenmap.audit_log
+    FOR EACH STATEMENT EXECUTE FUNCTION screenmap.audit_log_append_only();
@@ -0,0 +1,66 @@
+//! Queries a running screenmap server, by default the local one:
+//!     $ SCREENMAP_TOKEN=<token> cargo run --example query -- <screen> [<site>]
+//! It lists the screens, then the columns and first rows of <screen>, and the rows of <site> if
+//! given. Set SCREENMAP_URL to query another server.
+use screenmap_client::{CellValue, Client, CysQuery, DataRow, RowsQuery};
+use std::env;
+
+fn show(row: &DataRow) -> String {
+    let cells: Vec<String> = row
+        .cells
+        .iter()
+        .map(|(column, cell)| match cell {
+            CellValue::Int(value) => format!("{column}={value}"),
+            CellValue::Float(value) => format!("{column}={value}"),
+            CellValue::Text(value) => format!("{column}={value}"),
+            CellValue::Null => format!("{column}="),
+        })
+        .collect();
+    format!("{:>6}  {}", row.id, cells.join(" "))
+}
+
+#[tokio::main]
+async fn main() -> screenmap_client::Result<()> {
+    let mut args = env::args().skip(1);
+    let Some(screen) = args.next() else {
+        eprintln!("usage: query <screen> [<site>]");
+        std::process::exit(2);
+    };
+    let url = env::var("SCREENMAP_URL").unwrap_or_else(|_| "http://127.0.0.1:3000".to_string());
+    let mut client = Client::new(&url)?;
+    if let Ok(token) = env::var("SCREENMAP_TOKEN") {
+        client = client.with_token(token);
+    }
+
+    for info in client.screens().await? {
+        println!("{} ({} rows)", info.name, info.num_rows);
+    }
+
+    println!("\ncolumns of {screen}:");
+    for column in client.columns(&screen).await? {
+        println!(
+            "{:<30} {:<16} {}",
+            column.name,
+            column.col_type.as_str(),
+            column.roles.join(", ")
+        );
+    }
+
+    let page = client.rows(&screen, &RowsQuery::new().limit(5)).await?;
+    println!("\nfirst {} of {} rows:", page.rows.len(), page.total);
+    for row in &page.rows {
+        println!("{}", show(row));
+    }
+
+    if let Some(site) = args.next() {
+        let query = CysQuery {
+            cys_name: site.clone(),
+            screen_name: screen,
+        };
+        println!("\nrows of {site}:");
+        for row in client.cysteine(&query).await? {
+            println!("{}", show(&row));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,27 @@
+use reqwest::StatusCode;
+
+/// Why a request to the server failed.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Invalid server url {0:?}.")]
+    Url(String),
+    // the server couldn't be reached, or its answer couldn't be read
+    #[error("{0}")]
+    Http(#[from] reqwest::Error),
+    // the server refused the request, e.g. 401 without a valid token or 404 for a missing screen
+    #[error("{message} ({status})")]
+    Api { status: StatusCode, message: String },
+}
+
+impl Error {
+    /// The status the server answered with, if it answered.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Error::Api { status, .. } => Some(*status),
+            Error::Http(e) => e.status(),
+            Error::Url(_) => None,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,137 @@
+//! A typed client for the /v1 API of a screenmap server, for Rust tools that query screens
+//! directly. It shares interface.rs with the server, so screens, columns and rows come back as the
+//! same types the website uses.
+//!
+//! A [`Client`] is made with [`Client::new`] and, unless it can use a session, given an API token
+//! with the read scope by [`Client::with_token`]. [`Client::screens`] lists the screens,
+//! [`Client::columns`] describes their columns, [`Client::rows`] pages through rows matching a
+//! [`RowsQuery`] and [`Client::cysteine`] looks up the rows of a cysteine.
+mod error;
+// the server's own, so its lints are the server's business
+#[path = "../../screenmap/src/interface.rs"]
+#[allow(clippy::needless_return, clippy::should_implement_trait)]
+pub mod interface;
+mod rows;
+
+pub use error::{Error, Result};
+pub use interface::{
+    CellValue, ColType, ColumnInfo, CysQuery, DataCell, DataRow, ProteinContext, RowsPage,
+    ScreenDescription, ScreenInfo,
+};
+pub use rows::{Filter, Op, RowsQuery, SortOrder};
+
+use reqwest::Url;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+
+/// The most rows the server sends at once, which [`Client::all_rows`] fetches them by.
+const MAX_LIMIT: usize = 1000;
+
+/// How the server sends errors.
+#[derive(Deserialize)]
+struct ErrorBody {
+    error: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: Url,
+    token: Option<String>,
+}
+
+impl Client {
+    /// A client for the server at `base_url`, e.g. `http://127.0.0.1:3000`.
+    pub fn new(base_url: &str) -> Result<Self> {
+        let url = Url::parse(base_url)
+            .ok()
+            .filter(|url| !url.cannot_be_a_base())
+            .ok_or_else(|| Error::Url(base_url.to_string()))?;
+        Ok(Client {
+            http: reqwest::Client::new(),
+            base_url: url,
+            token: None,
+        })
+    }
+
+    /// Sends `token`, an API token made at /tokens, with every request.
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// The url of `segments` under /v1, each escaped as one path segment, so screen names and
+    /// sites like `sp|P04637|P53_HUMAN C176` can be passed as they are.
+    fn url(&self, segments: &[&str]) -> Url {
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .expect("checked by Client::new")
+            .pop_if_empty()
+            .push("v1")
+            .extend(segments);
+        url
+    }
+
+    async fn get<T: DeserializeOwned>(&self, url: Url, query: &[(&str, String)]) -> Result<T> {
+        let mut request = self.http.get(url).query(query);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response.json().await?);
+        }
+        // /v1 answers with an ErrorBody, but a token that isn't valid is refused in plain text
+        let body = response.text().await?;
+        let message = serde_json::from_str::<ErrorBody>(&body)
+            .map(|body| body.error)
+            .unwrap_or(body);
+        Err(Error::Api { status, message })
+    }
+
+    /// Every screen the token's user can read, archived ones included.
+    pub async fn screens(&self) -> Result<Vec<ScreenInfo>> {
+        self.get(self.url(&["screens"]), &[]).await
+    }
+
+    /// The metadata and columns of `screen`.
+    pub async fn describe(&self, screen: &str) -> Result<ScreenDescription> {
+        self.get(self.url(&["screens", screen]), &[]).await
+    }
+
+    /// The columns of `screen`, with their types, roles and ranges.
+    pub async fn columns(&self, screen: &str) -> Result<Vec<ColumnInfo>> {
+        Ok(self.describe(screen).await?.columns)
+    }
+
+    /// One page of the rows of `screen` matching `query`, with how many match in all.
+    pub async fn rows(&self, screen: &str, query: &RowsQuery) -> Result<RowsPage> {
+        self.get(self.url(&["screens", screen, "rows"]), &query.params())
+            .await
+    }
+
+    /// Every row of `screen` matching `query` from its offset on, fetched a page of its limit, or
+    /// else of 1000 rows, at a time.
+    pub async fn all_rows(&self, screen: &str, query: &RowsQuery) -> Result<Vec<DataRow>> {
+        let mut query = query.clone();
+        query.limit = Some(query.limit.unwrap_or(MAX_LIMIT));
+        let mut rows = vec![];
+        loop {
+            let page = self.rows(screen, &query).await?;
+            let done = page.rows.is_empty() || page.offset + page.rows.len() >= page.total;
+            query.offset += page.rows.len();
+            rows.extend(page.rows);
+            if done {
+                return Ok(rows);
+            }
+        }
+    }
+
+    /// The rows of `query.screen_name` for the cysteine `query.cys_name`, by the screen's key or
+    /// a site column, e.g. `P04637_C176`. Fails with 404 if there are none.
+    pub async fn cysteine(&self, query: &CysQuery) -> Result<Vec<DataRow>> {
+        let url = self.url(&["screens", &query.screen_name, "cysteines", &query.cys_name]);
+        self.get(url, &[]).await
+    }
+}
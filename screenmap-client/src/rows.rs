@@ -0,0 +1,126 @@
+use std::fmt::{self, Display};
+
+/// How a [`Filter`] compares a column with its value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// The value appears in the column, ignoring case. Only for text columns.
+    Contains,
+}
+
+impl Op {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Op::Eq => "eq",
+            Op::Ne => "ne",
+            Op::Lt => "lt",
+            Op::Le => "le",
+            Op::Gt => "gt",
+            Op::Ge => "ge",
+            Op::Contains => "contains",
+        }
+    }
+}
+
+impl Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A condition on a column, which the server parses the value of as the column's type.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Filter {
+    pub column: String,
+    pub op: Op,
+    pub value: String,
+}
+
+impl Display for Filter {
+    /// `<column>:<op>:<value>`, as the server reads it.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.column, self.op, self.value)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "asc",
+            SortOrder::Desc => "desc",
+        }
+    }
+}
+
+/// Which rows of a screen to fetch, built up like
+/// `RowsQuery::new().filter("ratio", Op::Gt, 2).sort("ratio", SortOrder::Desc)`. Without a sort,
+/// rows are in the order of the screen's file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RowsQuery {
+    /// Rows must match every filter.
+    pub filters: Vec<Filter>,
+    pub sort: Option<(String, SortOrder)>,
+    /// Rows per page, at most 1000. The server sends 100 if it isn't given.
+    pub limit: Option<usize>,
+    pub offset: usize,
+}
+
+impl RowsQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn filter(mut self, column: impl Into<String>, op: Op, value: impl ToString) -> Self {
+        self.filters.push(Filter {
+            column: column.into(),
+            op,
+            value: value.to_string(),
+        });
+        self
+    }
+
+    pub fn sort(mut self, column: impl Into<String>, order: SortOrder) -> Self {
+        self.sort = Some((column.into(), order));
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// The query string of /v1/screens/{screen}/rows.
+    pub(crate) fn params(&self) -> Vec<(&'static str, String)> {
+        let mut params: Vec<_> = self
+            .filters
+            .iter()
+            .map(|filter| ("filter", filter.to_string()))
+            .collect();
+        if let Some((column, order)) = &self.sort {
+            params.push(("sort", column.clone()));
+            params.push(("order", order.as_str().to_string()));
+        }
+        if let Some(limit) = self.limit {
+            params.push(("limit", limit.to_string()));
+        }
+        params.push(("offset", self.offset.to_string()));
+        params
+    }
+}
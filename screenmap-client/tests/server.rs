@@ -0,0 +1,114 @@
+//! Tests against a running screenmap server, ignored unless asked for:
+//!     $ SCREENMAP_TOKEN=<token> cargo test -- --ignored
+//! They only read, from the largest screen the token can see, which needs at least 3 rows. Set
+//! SCREENMAP_URL to test another server than the local one.
+use screenmap_client::{CellValue, Client, ColType, DataRow, Op, RowsQuery, ScreenInfo};
+use std::env;
+
+fn client() -> Client {
+    let url = env::var("SCREENMAP_URL").unwrap_or_else(|_| "http://127.0.0.1:3000".to_string());
+    let token = env::var("SCREENMAP_TOKEN").expect("Set SCREENMAP_TOKEN to a read token.");
+    Client::new(&url).unwrap().with_token(token)
+}
+
+async fn largest_screen(client: &Client) -> ScreenInfo {
+    let screen = client
+        .screens()
+        .await
+        .unwrap()
+        .into_iter()
+        .max_by_key(|screen| screen.num_rows)
+        .expect("The token can't see any screens.");
+    assert!(screen.num_rows >= 3, "{} has too few rows.", screen.name);
+    screen
+}
+
+fn ids(rows: &[DataRow]) -> Vec<usize> {
+    rows.iter().map(|row| row.id).collect()
+}
+
+#[tokio::test]
+#[ignore = "needs a running server"]
+async fn all_rows_fetches_every_page() {
+    let client = client();
+    let screen = largest_screen(&client).await;
+    let page = client
+        .rows(&screen.name, &RowsQuery::new().limit(1000))
+        .await
+        .unwrap();
+    assert_eq!(page.total, screen.num_rows);
+
+    // Pages of 2 rows, so the last one is short or the rows run out exactly at a page's end.
+    let paged = client
+        .all_rows(&screen.name, &RowsQuery::new().limit(2))
+        .await
+        .unwrap();
+    assert_eq!(ids(&paged), ids(&page.rows));
+
+    let from_second = client
+        .all_rows(&screen.name, &RowsQuery::new().limit(2).offset(1))
+        .await
+        .unwrap();
+    assert_eq!(ids(&from_second), ids(&page.rows[1..]));
+}
+
+#[tokio::test]
+#[ignore = "needs a running server"]
+async fn filters_reach_the_server_intact() {
+    let client = client();
+    let screen = largest_screen(&client).await;
+    let columns = client.columns(&screen.name).await.unwrap();
+    let rows = client
+        .rows(&screen.name, &RowsQuery::new().limit(1000))
+        .await
+        .unwrap()
+        .rows;
+    // A text value of the screen, preferably one with characters a query string has to escape.
+    let (column, value) = columns
+        .iter()
+        .filter(|column| column.col_type == ColType::TEXT)
+        .flat_map(|column| {
+            rows.iter()
+                .filter_map(|row| match row.cells.get(&column.name) {
+                    Some(CellValue::Text(value)) => Some((column.name.clone(), value.clone())),
+                    _ => None,
+                })
+        })
+        .max_by_key(|(_, value)| value.chars().filter(|c| !c.is_alphanumeric()).count())
+        .expect("The screen has no text values.");
+
+    let matching = client
+        .all_rows(
+            &screen.name,
+            &RowsQuery::new().filter(&column, Op::Eq, &value),
+        )
+        .await
+        .unwrap();
+    assert!(!matching.is_empty(), "No row has {column} = {value:?}.");
+    for row in &matching {
+        assert_eq!(
+            row.cells.get(&column),
+            Some(&CellValue::Text(value.clone()))
+        );
+    }
+
+    // contains ignores case.
+    let page = client
+        .rows(
+            &screen.name,
+            &RowsQuery::new().filter(&column, Op::Contains, value.to_uppercase()),
+        )
+        .await
+        .unwrap();
+    assert!(ids(&page.rows).contains(&matching[0].id));
+}
+
+#[tokio::test]
+#[ignore = "needs a running server"]
+async fn unknown_columns_are_refused() {
+    let client = client();
+    let screen = largest_screen(&client).await;
+    let query = RowsQuery::new().filter("no_such_column", Op::Eq, 1);
+    let e = client.rows(&screen.name, &query).await.unwrap_err();
+    assert_eq!(e.status().map(|status| status.as_u16()), Some(400));
+}
@@ -0,0 +1,248 @@
+//! Server side of the admin screens page, which renames, archives and deletes screens, moves
+//! them between projects and edits how they are shown. Editors of a screen can describe it and
+//! its admins can do the rest, except for moving it, which is for site admins.
+use crate::{error::AppError, interface::ScreenSummary};
+use leptos::server;
+use server_fn::codec::Json;
+
+type ServerFnResult<T> = Result<T, AppError>;
+
+cfg_if::cfg_if! {
+if #[cfg(feature = "ssr")] {
+    use crate::audit;
+    use crate::auth::{require_admin, require_user};
+    use crate::interface::{AuditAction, Role};
+    use crate::projects::{project_id, require_screen, screen_roles};
+    use crate::server::{AppState, count_rows, get_tbls};
+    use sqlx::{PgConnection, Row};
+    use std::collections::HashMap;
+
+    /// Screens loaded before imports were recorded get a row with no checksum, which no file
+    /// matches, so their metadata can be set.
+    async fn ensure_recorded(conn: &mut PgConnection, name: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO screenmap.screens (name, checksum) VALUES ($1, '') ON CONFLICT (name) DO NOTHING",
+        )
+        .bind(name)
+        .execute(conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Postgres's estimate of each screen's rows, from its last vacuum or analyze, so listing
+    /// screens doesn't scan them all. Tables never analyzed aren't estimated and are counted.
+    async fn estimated_rows(state: &AppState) -> Result<HashMap<String, usize>, AppError> {
+        let estimates: Vec<(String, f32)> = sqlx::query_as(
+            r#"
+            SELECT c.relname::TEXT, c.reltuples
+            FROM pg_class c
+            WHERE c.relnamespace = current_schema()::regnamespace AND c.relkind = 'r'
+            "#,
+        )
+        .fetch_all(state.pool.as_ref())
+        .await?;
+        let mut rows = HashMap::new();
+        for (name, reltuples) in estimates {
+            let num_rows = if reltuples < 0.0 {
+                count_rows(state, &name).await?
+            } else {
+                reltuples as usize
+            };
+            rows.insert(name, num_rows);
+        }
+        Ok(rows)
+    }
+}}
+
+/// Every screen the user can edit with its metadata and estimated row count, archived ones
+/// included.
+#[server(name = ListScreens, prefix = "/api")]
+pub async fn list_screens() -> ServerFnResult<Vec<ScreenSummary>> {
+    let user = require_user()?;
+    let state = AppState::from_cx()?;
+    let recorded = sqlx::query(
+        r#"
+        SELECT s.name, s.checksum, s.title, s.description,
+            to_char(s.imported_at, 'YYYY-MM-DD HH24:MI') AS imported_at,
+            s.archived_at IS NOT NULL AS archived, p.name AS project
+        FROM screenmap.screens s LEFT JOIN screenmap.projects p ON p.id = s.project_id
+        "#,
+    )
+    .fetch_all(state.pool.as_ref())
+    .await?;
+    let num_rows = estimated_rows(&state).await?;
+    let mut screens = vec![];
+    for (name, role) in screen_roles(&state, &user).await? {
+        if role < Role::Editor {
+            continue;
+        }
+        let row = recorded
+            .iter()
+            .find(|row| row.get::<String, _>("name") == name);
+        // A recorded row without a checksum was only made to hold metadata.
+        let imported = row.is_some_and(|row| !row.get::<String, _>("checksum").is_empty());
+        screens.push(ScreenSummary {
+            num_rows: num_rows.get(&name).copied().unwrap_or_default(),
+            title: row.and_then(|row| row.get("title")),
+            description: row.and_then(|row| row.get("description")),
+            imported_at: row
+                .filter(|_| imported)
+                .and_then(|row| row.get("imported_at")),
+            archived: row.is_some_and(|row| row.get("archived")),
+            project: row.and_then(|row| row.get("project")),
+            role,
+            name,
+        });
+    }
+    Ok(screens)
+}
+
+/// Renames the screen's table, which its metadata follows.
+#[server(name = RenameScreen, prefix = "/api")]
+pub async fn rename_screen(name: String, new_name: String) -> ServerFnResult<String> {
+    let state = AppState::from_cx()?;
+    require_screen(&state, &name, Role::Admin).await?;
+    let new_name = ingest::validate_table_name(&new_name)?;
+    if get_tbls(&state).await?.contains(&new_name) {
+        return Err(AppError::new(format!(
+            "There already is a screen {new_name:?}."
+        )));
+    }
+    let mut tx = state.pool.begin().await?;
+    sqlx::query(&format!("ALTER TABLE {name} RENAME TO {new_name}"))
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("UPDATE screenmap.screens SET name = $2 WHERE name = $1")
+        .bind(&name)
+        .bind(&new_name)
+        .execute(&mut *tx)
+        .await?;
+    let detail = format!("to {new_name}");
+    audit::record(&mut *tx, AuditAction::Rename, Some(&name), Some(&detail)).await?;
+    tx.commit().await?;
+    Ok(new_name)
+}
+
+/// Archives the screen, which hides it from the search, or restores it.
+#[server(name = ArchiveScreen, prefix = "/api")]
+pub async fn archive_screen(name: String, archived: bool) -> ServerFnResult<()> {
+    let state = AppState::from_cx()?;
+    require_screen(&state, &name, Role::Admin).await?;
+    let mut tx = state.pool.begin().await?;
+    ensure_recorded(&mut tx, &name).await?;
+    sqlx::query(
+        r#"
+        UPDATE screenmap.screens
+        SET archived_at = CASE WHEN $2 THEN COALESCE(archived_at, now()) END
+        WHERE name = $1
+        "#,
+    )
+    .bind(&name)
+    .bind(archived)
+    .execute(&mut *tx)
+    .await?;
+    let action = if archived {
+        AuditAction::Archive
+    } else {
+        AuditAction::Restore
+    };
+    audit::record(&mut *tx, action, Some(&name), None).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Drops the screen for good. Only archived screens can be deleted.
+#[server(name = DeleteScreen, prefix = "/api")]
+pub async fn delete_screen(name: String) -> ServerFnResult<()> {
+    let state = AppState::from_cx()?;
+    require_screen(&state, &name, Role::Admin).await?;
+    let archived: Option<bool> =
+        sqlx::query_scalar("SELECT archived_at IS NOT NULL FROM screenmap.screens WHERE name = $1")
+            .bind(&name)
+            .fetch_optional(state.pool.as_ref())
+            .await?;
+    if archived != Some(true) {
+        return Err(AppError::new("Archive the screen before deleting it."));
+    }
+    let mut tx = state.pool.begin().await?;
+    sqlx::query(&format!("DROP TABLE {name}"))
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM screenmap.screens WHERE name = $1")
+        .bind(&name)
+        .execute(&mut *tx)
+        .await?;
+    audit::record(&mut *tx, AuditAction::Delete, Some(&name), None).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Sets the title and description of the screen and the names shown for its columns. Empty
+/// values are cleared.
+#[server(name = EditScreen, prefix = "/api", input = Json)]
+pub async fn edit_screen(
+    name: String,
+    title: String,
+    description: String,
+    display_names: Vec<(String, String)>,
+) -> ServerFnResult<()> {
+    let state = AppState::from_cx()?;
+    require_screen(&state, &name, Role::Editor).await?;
+    let non_empty = |s: String| Some(s.trim().to_string()).filter(|s| !s.is_empty());
+    let (columns, display_names): (Vec<_>, Vec<_>) = display_names
+        .into_iter()
+        .filter_map(|(column, display_name)| Some((column, non_empty(display_name)?)))
+        .unzip();
+    let mut tx = state.pool.begin().await?;
+    ensure_recorded(&mut tx, &name).await?;
+    sqlx::query("UPDATE screenmap.screens SET title = $2, description = $3 WHERE name = $1")
+        .bind(&name)
+        .bind(non_empty(title))
+        .bind(non_empty(description))
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM screenmap.column_display_names WHERE screen = $1")
+        .bind(&name)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        r#"
+        INSERT INTO screenmap.column_display_names (screen, column_name, display_name)
+        SELECT $1, * FROM UNNEST($2::TEXT[], $3::TEXT[])
+        "#,
+    )
+    .bind(&name)
+    .bind(columns)
+    .bind(display_names)
+    .execute(&mut *tx)
+    .await?;
+    audit::record(&mut *tx, AuditAction::Edit, Some(&name), None).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Moves the screen into `project`, which only its members can see, or publishes it with None.
+#[server(name = SetScreenProject, prefix = "/api")]
+pub async fn set_screen_project(name: String, project: Option<String>) -> ServerFnResult<()> {
+    require_admin()?;
+    let state = AppState::from_cx()?;
+    require_screen(&state, &name, Role::Admin).await?;
+    let project_id = match &project {
+        Some(project) => Some(project_id(&state, project).await?),
+        None => None,
+    };
+    let mut tx = state.pool.begin().await?;
+    ensure_recorded(&mut tx, &name).await?;
+    sqlx::query("UPDATE screenmap.screens SET project_id = $2 WHERE name = $1")
+        .bind(&name)
+        .bind(project_id)
+        .execute(&mut *tx)
+        .await?;
+    let detail = match &project {
+        Some(project) => format!("into {project}"),
+        None => "published".to_string(),
+    };
+    audit::record(&mut *tx, AuditAction::Move, Some(&name), Some(&detail)).await?;
+    tx.commit().await?;
+    Ok(())
+}
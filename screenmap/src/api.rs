@@ -0,0 +1,494 @@
+//! The /v1 REST API, a stable json interface to the screens for scripts and other services, as
+//! opposed to the server functions under /api, whose names and encoding follow the website.
+//! Requests are let in like the website's, by session cookie or an API token with the read scope,
+//! and /v1/openapi.json describes the API.
+use crate::audit;
+use crate::auth::CurrentUser;
+use crate::error::AppError;
+use crate::interface::{
+    AuditAction, ColType, ColumnInfo, DataCell, DataRow, ProteinContext, Role, RowsPage, Scope,
+    ScreenDescription, ScreenInfo,
+};
+use crate::projects::{check_screen, screen_roles};
+use crate::server::{
+    AppState, count_rows, fetch_rows, file_order, get_columns, get_screen_keys_inner, key_column,
+};
+use axum::extract::{Extension, Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::{Json, Router, routing::get};
+use axum_extra::extract::{Query, QueryRejection};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use std::collections::{BTreeMap, HashMap};
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{IntoParams, Modify, OpenApi, ToSchema};
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+/// Rows per page unless `limit` is given, and the most it can be.
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 1000;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "screenmap",
+        version = "1",
+        description = "Read access to the screens of a screenmap server. Send an API token made \
+            at /tokens as `Authorization: Bearer <token>`."
+    ),
+    modifiers(&BearerToken),
+    security(("token" = [])),
+    tags((name = "screens"))
+)]
+struct ApiDoc;
+
+struct BearerToken;
+
+impl Modify for BearerToken {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "token",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+            );
+        }
+    }
+}
+
+/// The routes under /v1, with the OpenAPI document at /v1/openapi.json.
+pub fn router() -> Router<AppState> {
+    let (router, openapi) = OpenApiRouter::with_openapi(ApiDoc::openapi())
+        .routes(routes!(list_screens))
+        .routes(routes!(describe_screen))
+        .routes(routes!(query_rows))
+        .routes(routes!(find_cysteine))
+        .split_for_parts();
+    router.route(
+        "/v1/openapi.json",
+        get(move || async move { Json(openapi) }),
+    )
+}
+
+/// How every error is sent, with a status to match.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ErrorBody {
+    pub error: String,
+}
+
+pub enum ApiError {
+    App(AppError),
+    /// The request can't be served as asked, e.g. a filter on a column the screen doesn't have.
+    BadRequest(String),
+    NotFound(String),
+}
+
+impl<E: Into<AppError>> From<E> for ApiError {
+    fn from(value: E) -> Self {
+        ApiError::App(value.into())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, error) = match self {
+            ApiError::App(e) => (e.status(), e.to_string()),
+            ApiError::BadRequest(error) => (StatusCode::BAD_REQUEST, error),
+            ApiError::NotFound(error) => (StatusCode::NOT_FOUND, error),
+        };
+        (status, Json(ErrorBody { error })).into_response()
+    }
+}
+
+/// Fails unless the request may read `screen`, which must exist.
+async fn require_screen(
+    state: &AppState,
+    current: &CurrentUser,
+    screen: &str,
+) -> Result<(), ApiError> {
+    let user = current.require(Scope::Read)?;
+    check_screen(state, user, screen, Role::Viewer).await?;
+    Ok(())
+}
+
+/// Every screen the caller can read, archived ones included.
+#[utoipa::path(
+    get,
+    path = "/v1/screens",
+    tag = "screens",
+    responses(
+        (status = 200, body = Vec<ScreenInfo>),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+    )
+)]
+async fn list_screens(
+    Extension(current): Extension<CurrentUser>,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<ScreenInfo>>, ApiError> {
+    let user = current.require(Scope::Read)?;
+    let recorded = sqlx::query(
+        r#"
+        SELECT s.name, s.title, s.description, s.archived_at IS NOT NULL AS archived,
+            p.name AS project
+        FROM screenmap.screens s LEFT JOIN screenmap.projects p ON p.id = s.project_id
+        "#,
+    )
+    .fetch_all(state.pool.as_ref())
+    .await?;
+    let mut screens = vec![];
+    for name in screen_roles(&state, user).await?.into_keys() {
+        let row = recorded
+            .iter()
+            .find(|row| row.get::<String, _>("name") == name);
+        screens.push(ScreenInfo {
+            num_rows: count_rows(&state, &name).await?,
+            title: row.and_then(|row| row.get("title")),
+            description: row.and_then(|row| row.get("description")),
+            project: row.and_then(|row| row.get("project")),
+            archived: row.is_some_and(|row| row.get("archived")),
+            name,
+        });
+    }
+    Ok(Json(screens))
+}
+
+/// The screen's metadata and columns, with what they hold and the range of the numeric ones.
+#[utoipa::path(
+    get,
+    path = "/v1/screens/{screen}",
+    tag = "screens",
+    params(("screen" = String, Path)),
+    responses(
+        (status = 200, body = ScreenDescription),
+        (status = 403, description = "No access to the screen", body = ErrorBody),
+        (status = 404, description = "No such screen", body = ErrorBody),
+    )
+)]
+async fn describe_screen(
+    Path(screen): Path<String>,
+    Extension(current): Extension<CurrentUser>,
+    State(state): State<AppState>,
+) -> Result<Json<ScreenDescription>, ApiError> {
+    require_screen(&state, &current, &screen).await?;
+    let (title, description): (Option<String>, Option<String>) =
+        sqlx::query_as("SELECT title, description FROM screenmap.screens WHERE name = $1")
+            .bind(&screen)
+            .fetch_optional(state.pool.as_ref())
+            .await?
+            .unwrap_or_default();
+    let display_names: HashMap<String, String> = sqlx::query_as(
+        "SELECT column_name, display_name FROM screenmap.column_display_names WHERE screen = $1",
+    )
+    .bind(&screen)
+    .fetch_all(state.pool.as_ref())
+    .await?
+    .into_iter()
+    .collect();
+    let roles: Vec<(String, String)> = sqlx::query_as(
+        "SELECT column_name, role FROM screenmap.screen_columns WHERE screen = $1 ORDER BY role",
+    )
+    .bind(&screen)
+    .fetch_all(state.pool.as_ref())
+    .await?;
+    let columns = get_screen_keys_inner(&screen, &state)
+        .await?
+        .into_iter()
+        .map(|(name, col_type, range)| ColumnInfo {
+            display_name: display_names.get(&name).cloned(),
+            roles: roles
+                .iter()
+                .filter(|(column, _)| *column == name)
+                .map(|(_, role)| role.clone())
+                .collect(),
+            min: range.map(|(min, _)| min),
+            max: range.map(|(_, max)| max),
+            col_type,
+            name,
+        })
+        .collect();
+    Ok(Json(ScreenDescription {
+        key_column: key_column(&screen, &state).await.ok(),
+        num_rows: count_rows(&state, &screen).await?,
+        name: screen,
+        title,
+        description,
+        columns,
+    }))
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RowsQuery {
+    /// `<column>:<op>:<value>`, where op is one of eq, ne, lt, le, gt, ge or contains (text
+    /// columns only, ignoring case), e.g. `gene:eq:TP53` or `ratio:gt:2`. Rows must match every
+    /// filter.
+    #[serde(default)]
+    #[param(example = json!(["ratio:gt:2"]))]
+    pub filter: Vec<String>,
+    /// The column to sort by, with empty values last. Rows are otherwise in the order of the
+    /// screen's file.
+    pub sort: Option<String>,
+    #[param(inline)]
+    pub order: Option<SortOrder>,
+    /// Rows per page, at most 1000. Defaults to 100.
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+/// A value to compare a column with, parsed as the column's type.
+enum Bound {
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+/// `"column"` as an SQL identifier. Columns are checked to exist before they are quoted.
+fn quote(column: &str) -> String {
+    format!("\"{}\"", column.replace('"', "\"\""))
+}
+
+/// The condition of a `<column>:<op>:<value>` filter on `columns`, with its value as `$param`.
+fn parse_filter(
+    filter: &str,
+    columns: &[(String, ColType)],
+    param: usize,
+) -> Result<(String, Bound), ApiError> {
+    let mut parts = filter.splitn(3, ':');
+    let (Some(column), Some(op), Some(value)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(ApiError::BadRequest(format!(
+            "Filter {filter:?} isn't <column>:<op>:<value>."
+        )));
+    };
+    let Some((_, col_type)) = columns.iter().find(|(name, _)| name == column) else {
+        return Err(ApiError::BadRequest(format!(
+            "There is no column {column:?}."
+        )));
+    };
+    let operator = match op {
+        "eq" => "=",
+        "ne" => "<>",
+        "lt" => "<",
+        "le" => "<=",
+        "gt" => ">",
+        "ge" => ">=",
+        "contains" if *col_type == ColType::TEXT => "ILIKE",
+        "contains" => {
+            return Err(ApiError::BadRequest(format!(
+                "Only text columns can be filtered with contains, and {column:?} isn't one."
+            )));
+        }
+        _ => {
+            return Err(ApiError::BadRequest(format!(
+                "Unknown filter operator {op:?}, use eq, ne, lt, le, gt, ge or contains."
+            )));
+        }
+    };
+    let not_a = |kind: &str| {
+        ApiError::BadRequest(format!("{column:?} holds {kind}, which {value:?} isn't."))
+    };
+    let bound = match col_type {
+        ColType::SMALLINT | ColType::INT | ColType::BIGINT => {
+            Bound::Int(value.parse().map_err(|_| not_a("integers"))?)
+        }
+        ColType::REAL | ColType::DOUBLE => {
+            Bound::Float(value.parse().map_err(|_| not_a("numbers"))?)
+        }
+        ColType::TEXT if op == "contains" => Bound::Text(format!(
+            "%{}%",
+            value
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        )),
+        ColType::TEXT => Bound::Text(value.to_string()),
+    };
+    Ok((format!("{} {operator} ${param}", quote(column)), bound))
+}
+
+/// A page of the screen's rows that match every filter, in the order asked for.
+#[utoipa::path(
+    get,
+    path = "/v1/screens/{screen}/rows",
+    tag = "screens",
+    params(("screen" = String, Path), RowsQuery),
+    responses(
+        (status = 200, body = RowsPage),
+        (status = 400, description = "An invalid filter, sort or limit", body = ErrorBody),
+        (status = 403, description = "No access to the screen", body = ErrorBody),
+        (status = 404, description = "No such screen", body = ErrorBody),
+    )
+)]
+async fn query_rows(
+    Path(screen): Path<String>,
+    query: Result<Query<RowsQuery>, QueryRejection>,
+    Extension(current): Extension<CurrentUser>,
+    State(state): State<AppState>,
+) -> Result<Json<RowsPage>, ApiError> {
+    let Query(query) = query.map_err(|e| ApiError::BadRequest(e.body_text()))?;
+    require_screen(&state, &current, &screen).await?;
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+    if limit > MAX_LIMIT {
+        return Err(ApiError::BadRequest(format!(
+            "The limit is at most {MAX_LIMIT} rows."
+        )));
+    }
+    let offset = query.offset.unwrap_or(0);
+    let columns = get_columns(&screen, &state).await?;
+    let (conditions, values): (Vec<_>, Vec<_>) = query
+        .filter
+        .iter()
+        .enumerate()
+        .map(|(i, filter)| parse_filter(filter, &columns, i + 1))
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .unzip();
+    let where_clause = if conditions.is_empty() {
+        "TRUE".to_string()
+    } else {
+        conditions.join(" AND ")
+    };
+    let mut order_by = vec![];
+    if let Some(sort) = &query.sort {
+        if !columns.iter().any(|(name, _)| name == sort) {
+            return Err(ApiError::BadRequest(format!(
+                "There is no column {sort:?}."
+            )));
+        }
+        let order = match query.order.unwrap_or_default() {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        };
+        order_by.push(format!("{} {order} NULLS LAST", quote(sort)));
+    }
+    order_by.extend(file_order(&columns).into_iter().map(String::from));
+
+    let count_sql = format!("SELECT count(*) FROM {screen} WHERE {where_clause}");
+    let ids_sql = format!(
+        "SELECT id FROM {screen} WHERE {where_clause} ORDER BY {} LIMIT {limit} OFFSET {offset}",
+        order_by.join(", ")
+    );
+    let mut count = sqlx::query_scalar::<_, i64>(&count_sql);
+    let mut ids = sqlx::query_scalar::<_, i32>(&ids_sql);
+    for value in &values {
+        (count, ids) = match value {
+            Bound::Int(x) => (count.bind(*x), ids.bind(*x)),
+            Bound::Float(x) => (count.bind(*x), ids.bind(*x)),
+            Bound::Text(x) => (count.bind(x.clone()), ids.bind(x.clone())),
+        };
+    }
+    let total = count.fetch_one(state.pool.as_ref()).await?;
+    let ids: Vec<usize> = ids
+        .fetch_all(state.pool.as_ref())
+        .await?
+        .into_iter()
+        .map(|id| id as usize)
+        .collect();
+    // fetch_rows returns them in file order, so they are put back in the order asked for.
+    let position: HashMap<usize, usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+    let mut rows: Vec<DataRow> = fetch_rows(&state, &screen, &columns, &ids)
+        .await?
+        .into_iter()
+        .map(data_row)
+        .collect();
+    rows.sort_by_key(|row| position.get(&row.id).copied());
+    // Paging through a screen exports it as much as /export does.
+    if !rows.is_empty() {
+        let mut detail = format!(
+            "/v1 rows {} to {} of {total}",
+            offset + 1,
+            offset + rows.len()
+        );
+        if !query.filter.is_empty() {
+            detail.push_str(&format!(" matching {}", query.filter.join(", ")));
+        }
+        audit::record_as(
+            state.pool.as_ref(),
+            &current,
+            AuditAction::Export,
+            Some(&screen),
+            Some(&detail),
+        )
+        .await?;
+    }
+    Ok(Json(RowsPage {
+        total: total as usize,
+        offset,
+        rows,
+    }))
+}
+
+fn data_row((id, cells, protein): (usize, BTreeMap<String, DataCell>, ProteinContext)) -> DataRow {
+    let cells = cells
+        .into_iter()
+        .map(|(column, cell)| (column, cell.into()))
+        .collect();
+    DataRow { id, cells, protein }
+}
+
+/// The rows of the cysteine `site`, e.g. P04637_C176, in the order of the screen's file: the row
+/// keyed by it, or every row of the site in screens with a row per peptide or PSM.
+#[utoipa::path(
+    get,
+    path = "/v1/screens/{screen}/cysteines/{site}",
+    tag = "screens",
+    params(("screen" = String, Path), ("site" = String, Path)),
+    responses(
+        (status = 200, body = Vec<DataRow>),
+        (status = 403, description = "No access to the screen", body = ErrorBody),
+        (status = 404, description = "No such screen or cysteine", body = ErrorBody),
+    )
+)]
+async fn find_cysteine(
+    Path((screen, site)): Path<(String, String)>,
+    Extension(current): Extension<CurrentUser>,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<DataRow>>, ApiError> {
+    require_screen(&state, &current, &screen).await?;
+    let mut site_columns: Vec<String> = sqlx::query_scalar(
+        "SELECT column_name FROM screenmap.screen_columns WHERE screen = $1 AND role = 'site'",
+    )
+    .bind(&screen)
+    .fetch_all(state.pool.as_ref())
+    .await?;
+    site_columns.extend(key_column(&screen, &state).await.ok());
+    let columns = get_columns(&screen, &state).await?;
+    let condition = site_columns
+        .iter()
+        .filter(|column| columns.iter().any(|(name, _)| name == *column))
+        .map(|column| format!("{}::TEXT = $1", quote(column)))
+        .collect::<Vec<_>>()
+        .join(" OR ");
+    let ids: Vec<usize> = if condition.is_empty() {
+        vec![]
+    } else {
+        sqlx::query_scalar::<_, i32>(&format!(
+            "SELECT id FROM {screen} WHERE {condition} LIMIT {MAX_LIMIT}"
+        ))
+        .bind(&site)
+        .fetch_all(state.pool.as_ref())
+        .await?
+        .into_iter()
+        .map(|id| id as usize)
+        .collect()
+    };
+    if ids.is_empty() {
+        return Err(ApiError::NotFound(format!(
+            "There is no cysteine {site:?} in {screen}."
+        )));
+    }
+    Ok(Json(
+        fetch_rows(&state, &screen, &columns, &ids)
+            .await?
+            .into_iter()
+            .map(data_row)
+            .collect(),
+    ))
+}
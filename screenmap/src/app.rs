@@ -1,14 +1,19 @@
-use crate::{interface::DataCell, server::{get_rows, get_screen_keys, search_table, search_tbls}};
+use crate::{admin::{archive_screen, list_screens, delete_screen, edit_screen, rename_screen, set_screen_project}, audit::{MAX_ENTRIES, list_audit}, auth::{Login, Logout, get_current_user}, error::AppError, interface::{AuditAction, AuditFilter, ColType, DataCell, Job, JobState, Project, ProteinContext, Role, SEQUENCE_WINDOW, ScreenSummary, Scope, UploadColumn, User}, projects::{create_project, delete_project, list_projects, remove_member, set_member}, tokens::{create_token, list_tokens, revoke_token}, server::{get_rows, get_screen_keys, get_screen_meta, search_table, search_tbls}, upload::{ImportUpload, upload_screen}};
 use futures::FutureExt;
-use leptos_use::{on_click_outside, use_resize_observer};
+use codee::string::JsonSerdeCodec;
+use leptos_use::{UseWebSocketReturn, on_click_outside, use_resize_observer, use_websocket};
 use leptos::{
-    html::Div, logging::{error, log}, prelude::*, reactive::signal::signal
+    html::Div, logging::{error, log}, prelude::*, reactive::signal::signal, task::spawn_local
 };
 use leptos_meta::{MetaTags, Stylesheet, Title, provide_meta_context};
 use leptos_router::{
-    StaticSegment,
-    components::{Route, Router, Routes},
+    SsrMode, StaticSegment,
+    components::{ProtectedRoute, Route, Router, Routes},
 };
+use web_sys::{FormData, HtmlFormElement, SubmitEvent, wasm_bindgen::JsCast};
+
+/// The logged in user, which App provides as context.
+type UserResource = Resource<Result<Option<User>, AppError>>;
 
 pub fn shell(options: LeptosOptions) -> impl IntoView {
     view! {
@@ -31,19 +36,136 @@ pub fn shell(options: LeptosOptions) -> impl IntoView {
 #[component]
 pub fn App() -> impl IntoView {
     provide_meta_context();
+    let login = ServerAction::<Login>::new();
+    let logout = ServerAction::<Logout>::new();
+    // Looked up again whenever someone logs in or out.
+    let user = Resource::new(
+        move || (login.version().get(), logout.version().get()),
+        |_| get_current_user(),
+    );
+    let logged_in = move || user.get().map(|user| user.ok().flatten().is_some());
+    let is_admin = move || {
+        user.get()
+            .map(|user| user.ok().flatten().is_some_and(|user| user.is_admin))
+    };
+    provide_context::<UserResource>(user);
     view! {
         <Stylesheet id="leptos" href="/pkg/screenmap.css" />
         <Title text="Welcome to Leptos" />
         <Router>
             <main class="app-container">
+                <Transition>
+                    <UserBar user logout />
+                </Transition>
                 <Routes fallback=|| "Page not found.".into_view()>
-                    <Route path=StaticSegment("") view=SearchTables />
+                    <Route path=StaticSegment("login") view=move || view! { <LoginPage login /> } />
+                    <ProtectedRoute
+                        path=StaticSegment("")
+                        view=SearchTables
+                        condition=logged_in
+                        redirect_path=|| "/login"
+                        ssr=SsrMode::Async
+                    />
+                    <ProtectedRoute
+                        path=(StaticSegment("admin"), StaticSegment("upload"))
+                        view=Upload
+                        condition=is_admin
+                        redirect_path=|| "/"
+                        ssr=SsrMode::Async
+                    />
+                    <ProtectedRoute
+                        path=(StaticSegment("admin"), StaticSegment("screens"))
+                        view=AdminScreens
+                        condition=logged_in
+                        redirect_path=|| "/login"
+                        ssr=SsrMode::Async
+                    />
+                    <ProtectedRoute
+                        path=StaticSegment("tokens")
+                        view=Tokens
+                        condition=logged_in
+                        redirect_path=|| "/login"
+                        ssr=SsrMode::Async
+                    />
+                    <ProtectedRoute
+                        path=(StaticSegment("admin"), StaticSegment("projects"))
+                        view=AdminProjects
+                        condition=logged_in
+                        redirect_path=|| "/login"
+                        ssr=SsrMode::Async
+                    />
+                    <ProtectedRoute
+                        path=(StaticSegment("admin"), StaticSegment("audit"))
+                        view=AdminAudit
+                        condition=is_admin
+                        redirect_path=|| "/"
+                        ssr=SsrMode::Async
+                    />
                 </Routes>
             </main>
         </Router>
     }
 }
 
+/// The logged in user, with links to the pages managing screens and projects.
+#[component]
+fn UserBar(user: UserResource, logout: ServerAction<Logout>) -> impl IntoView {
+    move || {
+        user.get().and_then(|user| user.ok().flatten()).map(|user| {
+            let upload_link = user.is_admin.then(|| view! { <a href="/admin/upload">"Upload"</a> });
+            let audit_link = user.is_admin.then(|| view! { <a href="/admin/audit">"Audit log"</a> });
+            view! {
+                <nav class="user-bar">
+                    <a href="/">"Screens"</a>
+                    {upload_link}
+                    <a href="/admin/screens">"Manage"</a>
+                    <a href="/admin/projects">"Projects"</a>
+                    <a href="/tokens">"Tokens"</a>
+                    {audit_link}
+                    <span>{user.username}</span>
+                    <ActionForm action=logout>
+                        <button type="submit" class="generic-box">"Log out"</button>
+                    </ActionForm>
+                </nav>
+            }
+        })
+    }
+}
+
+#[component]
+fn LoginPage(login: ServerAction<Login>) -> impl IntoView {
+    let error = move || {
+        login
+            .value()
+            .get()
+            .and_then(|result| result.err())
+            .map(|e| view! { <p class="error">{e.to_string()}</p> })
+    };
+    view! {
+        <div class="admin-page login-page">
+            <h2>"Log in"</h2>
+            <ActionForm action=login>
+                <input
+                    type="text"
+                    name="username"
+                    class="generic-box"
+                    placeholder="Username"
+                    autocomplete="username"
+                />
+                <input
+                    type="password"
+                    name="password"
+                    class="generic-box"
+                    placeholder="Password"
+                    autocomplete="current-password"
+                />
+                <button type="submit" class="generic-box">"Log in"</button>
+            </ActionForm>
+            {error}
+        </div>
+    }
+}
+
 #[component]
 fn SearchTables() -> impl IntoView {
     let (screen_name_0, set_screen_name_0) = signal(String::new());
@@ -189,6 +311,10 @@ fn Table(
         move || screen_name.get(),
         |screen_name| get_screen_keys(screen_name).map(|result| result.unwrap_or_default())
     );
+    let screen_meta = Resource::new(
+        move || screen_name.get(),
+        |screen_name| get_screen_meta(screen_name).map(|result| result.unwrap_or_default())
+    );
     let (cur_page, set_cur_page) = signal(0usize);
     Effect::new(move || {
         let _ = query();
@@ -233,10 +359,10 @@ fn Table(
             let mut cur_fetched = fetched_rows_iter.next();
             let mut viewable_rows = vec![];
             for i in rows_to_view {
-                if let Some((row_id, row)) = cur_fetched
+                if let Some((row_id, row, context)) = cur_fetched
                     && *row_id == i
                 {
-                    viewable_rows.push(row.clone());
+                    viewable_rows.push((row.clone(), context.clone()));
                     cur_fetched = fetched_rows_iter.next();
                 } else {
                     return Err(format!(
@@ -254,15 +380,31 @@ fn Table(
         set_num_rows(rows_getter.get().map_or(0, |k| k.map_or(0, |(_, _, total)| total)))
     });
 
+    // Screens with parsed cysteine identifiers get UniProt context columns in front of their own.
+    let has_sites = move || {
+        screen_keys
+            .get()
+            .is_some_and(|keys| keys.iter().any(|(key, _, _)| key == "cys_residue"))
+    };
+
     let table_header = move || match screen_keys.get() {
         Some(screen_keys) => {
+            let context_header = has_sites().then(|| view! {
+                <th>"Protein"</th>
+                <th>"Gene"</th>
+                <th>"Sequence"</th>
+            });
+            let display_names = screen_meta.get().unwrap_or_default().display_names;
             let header_inner = screen_keys
                 .into_iter()
-                .map(|(key, _, _)| view! { <th>{key}</th> })
+                .map(|(key, _, _)| {
+                    let name = display_names.get(&key).cloned().unwrap_or(key);
+                    view! { <th>{name}</th> }
+                })
                 .collect_view();
             view! {
                 <thead>
-                    <tr>{header_inner}</tr>
+                    <tr>{context_header}{header_inner}</tr>
                 </thead>
             }
             .into_any()
@@ -312,10 +454,24 @@ fn Table(
         view! { <td class="cell-border" style=style>{cell_data}</td> }
     };
 
+    let display_context = |context: ProteinContext| {
+        let window = context.window.map(|window| {
+            let (left, right) = window.split_at(SEQUENCE_WINDOW);
+            let (site, right) = right.split_at(1);
+            view! { {left.to_string()}<b>{site.to_string()}</b>{right.to_string()} }
+        });
+        view! {
+            <td class="cell-border">{context.protein_name}</td>
+            <td class="cell-border">{context.gene_name}</td>
+            <td class="cell-border sequence-window">{window}</td>
+        }
+    };
+
     let table_body = move || {
         match rows_getter.get() {
             Some(Ok((_, items, _))) => {
-                let rows = items.into_iter().map(|item| {
+                let rows = items.into_iter().map(|(item, context)| {
+                    let context = has_sites().then(|| display_context(context));
                     screen_keys
                         .get()
                         .unwrap_or_default()
@@ -323,7 +479,7 @@ fn Table(
                         .map(|(key, _, bound)| item.get(&key).map(move |item| (item, bound)))
                         .try_collect::<Vec<_>>()
                         .map(|row| view! {
-                            <tr> {
+                            <tr> {context} {
                                 row.into_iter().map(display_cell).collect_view()
                             } </tr> 
                         })
@@ -369,7 +525,7 @@ fn Table(
                             cur_page.get() * page_size.get() + 1,
                             std::cmp::min((cur_page.get() + 1) * page_size.get(), num_rows.get()),
                             num_rows.get(),
-                            screen_name.get()
+                            screen_meta.get().and_then(|meta| meta.title).unwrap_or_else(|| screen_name.get())
                         )
                     }</span>
                     <button
@@ -379,7 +535,18 @@ fn Table(
                     >
                         "Next"
                     </button>
+                    <a
+                        class="generic-box"
+                        href=move || format!("/export/{}", screen_name.get())
+                        download=""
+                        rel="external"
+                    >
+                        "Download"
+                    </a>
                 </div>
+                {move || screen_meta.get().and_then(|meta| meta.description).map(|description| view! {
+                    <p class="screen-description">{description}</p>
+                })}
                 <div class="scroll-container">
                     <ErrorBoundary fallback=|errors| {
                         view! {
@@ -402,3 +569,969 @@ fn Table(
         </div>
     }
 }
+
+
+/// Lets an admin upload a file, check the columns process_csv infers for it, rename and retype
+/// them, and import it as a screen, published or into a project.
+#[component]
+fn Upload() -> impl IntoView {
+    let (table, set_table) = signal(String::new());
+    let (replace, set_replace) = signal(false);
+    let (project, set_project) = signal(None::<String>);
+    let projects = Resource::new(|| (), |_| list_projects());
+    let columns = RwSignal::new(Vec::<UploadColumn>::new());
+    let upload = Action::new_local(|data: &FormData| upload_screen(data.clone().into()));
+    let import = ServerAction::<ImportUpload>::new();
+
+    // A new upload starts over from the inferred columns.
+    Effect::new(move || {
+        if let Some(Ok(preview)) = upload.value().get() {
+            set_table(preview.table.clone());
+            set_replace(false);
+            set_project(None);
+            columns.set(preview.columns.clone());
+        }
+    });
+
+    let on_submit = move |ev: SubmitEvent| {
+        ev.prevent_default();
+        let Some(form) = ev.target().and_then(|target| target.dyn_into::<HtmlFormElement>().ok()) else {
+            return;
+        };
+        match FormData::new_with_form(&form) {
+            Ok(data) => { upload.dispatch_local(data); }
+            Err(e) => error!("Failed to read the upload form: {e:?}"),
+        }
+    };
+
+    let column_rows = move || {
+        columns
+            .get()
+            .into_iter()
+            .enumerate()
+            .map(|(i, column)| {
+                let types = [ColType::BIGINT, ColType::DOUBLE, ColType::TEXT]
+                    .into_iter()
+                    .map(|col_type| view! {
+                        <option value=col_type.as_str() selected=col_type == column.col_type>
+                            {col_type.as_str()}
+                        </option>
+                    })
+                    .collect_view();
+                view! {
+                    <tr>
+                        <td class="cell-border">{column.header}</td>
+                        <td class="cell-border">
+                            <input
+                                type="text"
+                                class="generic-box"
+                                prop:value=column.name
+                                on:change=move |ev| {
+                                    let name = event_target_value(&ev);
+                                    columns.update(|columns| columns[i].name = name);
+                                }
+                            />
+                        </td>
+                        <td class="cell-border">
+                            <select
+                                class="generic-box"
+                                on:change=move |ev| {
+                                    if let Some(col_type) = ColType::from_str(&event_target_value(&ev)) {
+                                        columns.update(|columns| columns[i].col_type = col_type);
+                                    }
+                                }
+                            >
+                                {types}
+                            </select>
+                        </td>
+                    </tr>
+                }
+            })
+            .collect_view()
+    };
+
+    let preview = move || match upload.value().get() {
+        None => ().into_any(),
+        Some(Err(e)) => view! { <p class="error">{e.to_string()}</p> }.into_any(),
+        Some(Ok(preview)) => {
+            let upload_id = preview.upload_id.clone();
+            let sample_header = move || {
+                columns
+                    .get()
+                    .into_iter()
+                    .map(|column| view! { <th>{column.name}</th> })
+                    .collect_view()
+            };
+            let sample_rows = preview
+                .sample
+                .into_iter()
+                .map(|row| view! {
+                    <tr>
+                        {row.into_iter().map(|item| view! { <td class="cell-border">{item}</td> }).collect_view()}
+                    </tr>
+                })
+                .collect_view();
+            let replace_existing = preview.exists.then(|| view! {
+                <label>
+                    <input
+                        type="checkbox"
+                        prop:checked=replace
+                        on:change=move |ev| set_replace(event_target_checked(&ev))
+                    />
+                    {move || format!("Replace the existing screen {}", table.get())}
+                </label>
+            });
+            // A replaced screen stays in its project unless another is chosen.
+            let no_project = if preview.exists { "Keep its project" } else { "Published" };
+            let project_options = move || {
+                projects
+                    .get()
+                    .and_then(Result::ok)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|project| {
+                        let label = project.name.clone();
+                        view! { <option value=project.name>{label}</option> }
+                    })
+                    .collect_view()
+            };
+            view! {
+                <h3>{preview.file_name}</h3>
+                <div class="upload-table-name">
+                    <label>"Table "</label>
+                    <input
+                        type="text"
+                        class="generic-box"
+                        prop:value=table
+                        on:input=move |ev| set_table(event_target_value(&ev))
+                    />
+                    {replace_existing}
+                </div>
+                <div class="upload-table-name">
+                    <label>"Project "</label>
+                    <select
+                        class="generic-box"
+                        on:change=move |ev| set_project(Some(event_target_value(&ev)).filter(|project| !project.is_empty()))
+                    >
+                        <option value="" selected=move || project.get().is_none()>{no_project}</option>
+                        {project_options}
+                    </select>
+                </div>
+                <div class="scroll-container">
+                    <table class="bordered-table">
+                        <thead>
+                            <tr><th>"Header"</th><th>"Column"</th><th>"Type"</th></tr>
+                        </thead>
+                        <tbody>{column_rows}</tbody>
+                    </table>
+                </div>
+                <div class="scroll-container">
+                    <table class="bordered-table">
+                        <thead><tr>{sample_header}</tr></thead>
+                        <tbody>{sample_rows}</tbody>
+                    </table>
+                </div>
+                <button
+                    class="generic-box"
+                    disabled=move || import.pending().get()
+                    on:click=move |_| {
+                        import.dispatch(ImportUpload {
+                            upload_id: upload_id.clone(),
+                            table: table.get_untracked(),
+                            columns: columns.get_untracked(),
+                            replace: replace.get_untracked(),
+                            project: project.get_untracked(),
+                        });
+                    }
+                >
+                    "Import"
+                </button>
+            }
+            .into_any()
+        }
+    };
+
+    let import_result = move || match import.value().get() {
+        Some(Ok(job)) => view! { <JobProgress job /> }.into_any(),
+        Some(Err(e)) => view! { <p class="error">{e.to_string()}</p> }.into_any(),
+        None => ().into_any(),
+    };
+
+    view! {
+        <div class="admin-page">
+            <h2>"Upload a screen"</h2>
+            <form on:submit=on_submit>
+                <input type="file" name="file" class="generic-box" />
+                <button type="submit" class="generic-box" disabled=move || upload.pending().get()>
+                    "Preview"
+                </button>
+            </form>
+            {preview}
+            {import_result}
+        </div>
+    }
+}
+
+/// Follows an import running in the background until it succeeds or fails.
+#[component]
+fn JobProgress(job: Job) -> impl IntoView {
+    let UseWebSocketReturn { message, close, .. } =
+        use_websocket::<Job, Job, JsonSerdeCodec>(&format!("/ws/jobs/{}", job.id));
+    let job = Signal::derive(move || message.get().unwrap_or_else(|| job.clone()));
+
+    // The server keeps the socket open once the job is finished, so it isn't reconnected.
+    Effect::new(move || {
+        if job.get().state.is_finished() {
+            close();
+        }
+    });
+
+    move || {
+        let job = job.get();
+        match job.state {
+            JobState::Queued => view! { <p>{format!("Waiting to import {}...", job.file_name)}</p> }.into_any(),
+            JobState::Running => view! {
+                <p>{format!("Importing {} into {}: {} rows", job.file_name, job.table, job.rows_processed)}</p>
+            }
+            .into_any(),
+            JobState::Succeeded => view! {
+                <p>{format!("Imported {} into {} with {} rows.", job.file_name, job.table, job.rows_processed)}</p>
+            }
+            .into_any(),
+            JobState::Failed => view! {
+                <p class="error">{job.error.unwrap_or_else(|| "The import failed.".to_string())}</p>
+            }
+            .into_any(),
+        }
+    }
+}
+
+/// Lists the screens the user can edit, to describe them and, for their admins, to rename,
+/// archive or delete them. Site admins also move them between projects.
+#[component]
+fn AdminScreens() -> impl IntoView {
+    let user = expect_context::<UserResource>();
+    let is_admin = move || {
+        user.get()
+            .and_then(|user| user.ok().flatten())
+            .is_some_and(|user| user.is_admin)
+    };
+    let (message, set_message) = signal(None::<Result<String, String>>);
+    let (editing, set_editing) = signal(None::<String>);
+    let (version, set_version) = signal(0usize);
+    let screens = Resource::new(move || version.get(), |_| list_screens());
+    let projects = Resource::new(move || version.get(), |_| list_projects());
+    let project_names = Signal::derive(move || {
+        projects
+            .get()
+            .and_then(Result::ok)
+            .map(|projects| projects.into_iter().map(|project| project.name).collect())
+            .unwrap_or_default()
+    });
+    // Every change reloads the list, whether it worked or not.
+    let done = Callback::new(move |result: Result<String, String>| {
+        set_message(Some(result));
+        set_editing(None);
+        set_version.update(|version| *version += 1);
+    });
+
+    let screen_rows = move || match screens.get() {
+        None => view! { <p>"Loading..."</p> }.into_any(),
+        Some(Err(e)) => view! { <p class="error">{e.to_string()}</p> }.into_any(),
+        Some(Ok(screens)) if screens.is_empty() => {
+            view! { <p>"You can't edit any screens."</p> }.into_any()
+        }
+        Some(Ok(screens)) => {
+            let can_move = is_admin();
+            let rows = screens
+                .into_iter()
+                .map(|screen| {
+                    let row = view! {
+                        <ScreenRow screen=screen.clone() done set_editing can_move project_names />
+                    };
+                    let editor = move || (editing.get().as_ref() == Some(&screen.name)).then(|| view! {
+                        <tr>
+                            <td class="cell-border" colspan="7">
+                                <ScreenEditor screen=screen.clone() done />
+                            </td>
+                        </tr>
+                    });
+                    view! {
+                        {row}
+                        {editor}
+                    }
+                })
+                .collect_view();
+            view! {
+                <div class="scroll-container">
+                    <table class="bordered-table">
+                        <thead>
+                            <tr>
+                                <th>"Screen"</th>
+                                <th>"Title"</th>
+                                <th>"Project"</th>
+                                <th>"Rows (est.)"</th>
+                                <th>"Imported"</th>
+                                <th>"State"</th>
+                                <th>"Actions"</th>
+                            </tr>
+                        </thead>
+                        <tbody>{rows}</tbody>
+                    </table>
+                </div>
+            }
+            .into_any()
+        }
+    };
+
+    let message = move || match message.get() {
+        Some(Ok(message)) => view! { <p>{message}</p> }.into_any(),
+        Some(Err(e)) => view! { <p class="error">{e}</p> }.into_any(),
+        None => ().into_any(),
+    };
+
+    view! {
+        <div class="admin-page">
+            <h2>"Screens"</h2>
+            {message}
+            <Transition fallback=move || view! { <p>"Loading..."</p> }>{screen_rows}</Transition>
+        </div>
+    }
+}
+
+#[component]
+fn ScreenRow(
+    screen: ScreenSummary,
+    done: Callback<Result<String, String>>,
+    set_editing: WriteSignal<Option<String>>,
+    /// Whether the user can move the screen between projects, which are `project_names`.
+    can_move: bool,
+    project_names: Signal<Vec<String>>,
+) -> impl IntoView {
+    let name = StoredValue::new(screen.name.clone());
+    let (new_name, set_new_name) = signal(screen.name.clone());
+
+    let rename = move |_| {
+        let (name, new_name) = (name.get_value(), new_name.get_untracked());
+        spawn_local(async move {
+            let result = rename_screen(name.clone(), new_name).await;
+            done.run(result.map(|new_name| format!("Renamed {name} to {new_name}.")).map_err(|e| e.to_string()));
+        });
+    };
+    let archive = move |archived: bool| {
+        let name = name.get_value();
+        spawn_local(async move {
+            let result = archive_screen(name.clone(), archived).await;
+            let message = if archived { format!("Archived {name}.") } else { format!("Restored {name}.") };
+            done.run(result.map(|_| message).map_err(|e| e.to_string()));
+        });
+    };
+    let delete = move |_| {
+        let name = name.get_value();
+        let confirmed = window()
+            .confirm_with_message(&format!("Delete {name} and all of its rows for good?"))
+            .unwrap_or(false);
+        if !confirmed {
+            return;
+        }
+        spawn_local(async move {
+            let result = delete_screen(name.clone()).await;
+            done.run(result.map(|_| format!("Deleted {name}.")).map_err(|e| e.to_string()));
+        });
+    };
+
+    let move_to = move |project: Option<String>| {
+        let name = name.get_value();
+        spawn_local(async move {
+            let result = set_screen_project(name.clone(), project.clone()).await;
+            let message = match project {
+                Some(project) => format!("Moved {name} into {project}."),
+                None => format!("Published {name}."),
+            };
+            done.run(result.map(|_| message).map_err(|e| e.to_string()));
+        });
+    };
+
+    let project = if can_move {
+        let current = screen.project.clone().unwrap_or_default();
+        let published = current.is_empty();
+        let options = move || {
+            project_names
+                .get()
+                .into_iter()
+                .map(|project| {
+                    let selected = project == current;
+                    let label = project.clone();
+                    view! { <option value=project selected=selected>{label}</option> }
+                })
+                .collect_view()
+        };
+        view! {
+            <select
+                class="generic-box"
+                on:change=move |ev| move_to(Some(event_target_value(&ev)).filter(|project| !project.is_empty()))
+            >
+                <option value="" selected=published>"Published"</option>
+                {options}
+            </select>
+        }
+        .into_any()
+    } else {
+        screen.project.unwrap_or_else(|| "Published".to_string()).into_any()
+    };
+
+    let archived = screen.archived;
+    // Editors can only describe the screen.
+    let admin_actions = (screen.role == Role::Admin).then(|| view! {
+        <input
+            type="text"
+            class="generic-box"
+            prop:value=new_name
+            on:input=move |ev| set_new_name(event_target_value(&ev))
+        />
+        <button class="generic-box" on:click=rename>"Rename"</button>
+        <button class="generic-box" on:click=move |_| archive(!archived)>
+            {if archived { "Restore" } else { "Archive" }}
+        </button>
+        {archived.then(|| view! {
+            <button class="generic-box" on:click=delete>"Delete"</button>
+        })}
+    });
+    view! {
+        <tr>
+            <td class="cell-border">{screen.name}</td>
+            <td class="cell-border">{screen.title}</td>
+            <td class="cell-border">{project}</td>
+            <td class="cell-border">{screen.num_rows}</td>
+            <td class="cell-border">{screen.imported_at}</td>
+            <td class="cell-border">{if archived { "Archived" } else { "Active" }}</td>
+            <td class="cell-border admin-actions">
+                <button class="generic-box" on:click=move |_| set_editing(Some(name.get_value()))>
+                    "Edit"
+                </button>
+                {admin_actions}
+            </td>
+        </tr>
+    }
+}
+
+/// Edits the title and description of a screen and the names its columns are shown with.
+#[component]
+fn ScreenEditor(
+    screen: ScreenSummary,
+    done: Callback<Result<String, String>>,
+) -> impl IntoView {
+    let name = StoredValue::new(screen.name.clone());
+    let (title, set_title) = signal(screen.title.unwrap_or_default());
+    let (description, set_description) = signal(screen.description.unwrap_or_default());
+    let display_names = RwSignal::new(Vec::<(String, String)>::new());
+    let columns = LocalResource::new(move || {
+        let name = name.get_value();
+        async move {
+            let keys = get_screen_keys(name.clone()).await?;
+            let meta = get_screen_meta(name).await?;
+            Ok::<_, AppError>(
+                keys.into_iter()
+                    .map(|(column, _, _)| {
+                        let display_name = meta.display_names.get(&column).cloned().unwrap_or_default();
+                        (column, display_name)
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        }
+    });
+    Effect::new(move || {
+        if let Some(Ok(columns)) = columns.get() {
+            display_names.set(columns);
+        }
+    });
+
+    let save = move |_| {
+        let name = name.get_value();
+        spawn_local(async move {
+            let result = edit_screen(
+                name.clone(),
+                title.get_untracked(),
+                description.get_untracked(),
+                display_names.get_untracked(),
+            )
+            .await;
+            done.run(result.map(|_| format!("Saved {name}.")).map_err(|e| e.to_string()));
+        });
+    };
+
+    let column_rows = move || {
+        display_names
+            .get()
+            .into_iter()
+            .enumerate()
+            .map(|(i, (column, display_name))| view! {
+                <tr>
+                    <td class="cell-border">{column}</td>
+                    <td class="cell-border">
+                        <input
+                            type="text"
+                            class="generic-box"
+                            prop:value=display_name
+                            on:change=move |ev| {
+                                let display_name = event_target_value(&ev);
+                                display_names.update(|names| names[i].1 = display_name);
+                            }
+                        />
+                    </td>
+                </tr>
+            })
+            .collect_view()
+    };
+
+    view! {
+        <div class="screen-editor">
+            <label>"Title "</label>
+            <input
+                type="text"
+                class="generic-box"
+                prop:value=title
+                on:input=move |ev| set_title(event_target_value(&ev))
+            />
+            <label>"Description"</label>
+            <textarea
+                class="generic-box"
+                prop:value=description
+                on:input=move |ev| set_description(event_target_value(&ev))
+            />
+            {move || match columns.get() {
+                Some(Err(e)) => view! { <p class="error">{e.to_string()}</p> }.into_any(),
+                _ => view! {
+                    <table class="bordered-table">
+                        <thead>
+                            <tr><th>"Column"</th><th>"Shown as"</th></tr>
+                        </thead>
+                        <tbody>{column_rows}</tbody>
+                    </table>
+                }
+                .into_any(),
+            }}
+            <button class="generic-box" on:click=save>"Save"</button>
+        </div>
+    }
+}
+
+/// Lists the projects the user is an admin of, to add and remove their members. Site admins see
+/// every project and also create and delete them.
+#[component]
+fn AdminProjects() -> impl IntoView {
+    let user = expect_context::<UserResource>();
+    let is_admin = move || {
+        user.get()
+            .and_then(|user| user.ok().flatten())
+            .is_some_and(|user| user.is_admin)
+    };
+    let (message, set_message) = signal(None::<Result<String, String>>);
+    let (version, set_version) = signal(0usize);
+    let (new_project, set_new_project) = signal(String::new());
+    let projects = Resource::new(move || version.get(), |_| list_projects());
+    let done = Callback::new(move |result: Result<String, String>| {
+        set_message(Some(result));
+        set_version.update(|version| *version += 1);
+    });
+
+    let create = move |_| {
+        let name = new_project.get_untracked();
+        spawn_local(async move {
+            let result = create_project(name.clone()).await;
+            if result.is_ok() {
+                set_new_project(String::new());
+            }
+            done.run(result.map(|_| format!("Created {name}.")).map_err(|e| e.to_string()));
+        });
+    };
+
+    let project_list = move || match projects.get() {
+        None => view! { <p>"Loading..."</p> }.into_any(),
+        Some(Err(e)) => view! { <p class="error">{e.to_string()}</p> }.into_any(),
+        Some(Ok(projects)) if projects.is_empty() => {
+            view! { <p>"You don't manage any projects."</p> }.into_any()
+        }
+        Some(Ok(projects)) => {
+            let can_delete = is_admin();
+            projects
+                .into_iter()
+                .map(|project| view! { <ProjectCard project done can_delete /> })
+                .collect_view()
+                .into_any()
+        }
+    };
+
+    let message = move || match message.get() {
+        Some(Ok(message)) => view! { <p>{message}</p> }.into_any(),
+        Some(Err(e)) => view! { <p class="error">{e}</p> }.into_any(),
+        None => ().into_any(),
+    };
+
+    view! {
+        <div class="admin-page">
+            <h2>"Projects"</h2>
+            {message}
+            <Show when=is_admin>
+                <div class="admin-actions">
+                    <input
+                        type="text"
+                        class="generic-box"
+                        placeholder="New project"
+                        prop:value=new_project
+                        on:input=move |ev| set_new_project(event_target_value(&ev))
+                    />
+                    <button class="generic-box" on:click=create>"Create"</button>
+                </div>
+            </Show>
+            <Transition fallback=move || view! { <p>"Loading..."</p> }>{project_list}</Transition>
+        </div>
+    }
+}
+
+/// A project with its screens and members, which can be added, given another role or removed.
+#[component]
+fn ProjectCard(
+    project: Project,
+    done: Callback<Result<String, String>>,
+    can_delete: bool,
+) -> impl IntoView {
+    let name = StoredValue::new(project.name.clone());
+    let (username, set_username) = signal(String::new());
+    let (role, set_role) = signal(Role::Viewer);
+
+    let add = move |_| {
+        let (name, username, role) = (name.get_value(), username.get_untracked(), role.get_untracked());
+        spawn_local(async move {
+            let result = set_member(name.clone(), username.clone(), role).await;
+            done.run(result.map(|_| format!("Made {username} {role} of {name}.")).map_err(|e| e.to_string()));
+        });
+    };
+    let remove = move |username: String| {
+        let name = name.get_value();
+        spawn_local(async move {
+            let result = remove_member(name.clone(), username.clone()).await;
+            done.run(result.map(|_| format!("Removed {username} from {name}.")).map_err(|e| e.to_string()));
+        });
+    };
+    let delete = move |_| {
+        let name = name.get_value();
+        spawn_local(async move {
+            let result = delete_project(name.clone()).await;
+            done.run(result.map(|_| format!("Deleted {name}.")).map_err(|e| e.to_string()));
+        });
+    };
+
+    let screens = if project.screens.is_empty() {
+        "none yet".to_string()
+    } else {
+        project.screens.join(", ")
+    };
+    let members = project
+        .members
+        .into_iter()
+        .map(|(username, role)| {
+            let member = username.clone();
+            view! {
+                <tr>
+                    <td class="cell-border">{username}</td>
+                    <td class="cell-border">{role.as_str()}</td>
+                    <td class="cell-border">
+                        <button class="generic-box" on:click=move |_| remove(member.clone())>
+                            "Remove"
+                        </button>
+                    </td>
+                </tr>
+            }
+        })
+        .collect_view();
+    let role_options = Role::ALL
+        .into_iter()
+        .map(|role| view! { <option value=role.as_str()>{role.as_str()}</option> })
+        .collect_view();
+
+    view! {
+        <div class="project">
+            <h3>{project.name}</h3>
+            <p>"Screens: " {screens}</p>
+            <table class="bordered-table">
+                <thead>
+                    <tr><th>"Member"</th><th>"Role"</th><th></th></tr>
+                </thead>
+                <tbody>{members}</tbody>
+            </table>
+            <div class="admin-actions">
+                <input
+                    type="text"
+                    class="generic-box"
+                    placeholder="Username"
+                    prop:value=username
+                    on:input=move |ev| set_username(event_target_value(&ev))
+                />
+                <select
+                    class="generic-box"
+                    on:change=move |ev| {
+                        if let Some(role) = Role::parse(&event_target_value(&ev)) {
+                            set_role(role);
+                        }
+                    }
+                >
+                    {role_options}
+                </select>
+                <button class="generic-box" on:click=add>"Add"</button>
+                {can_delete.then(|| view! {
+                    <button class="generic-box" on:click=delete>"Delete project"</button>
+                })}
+            </div>
+        </div>
+    }
+}
+
+/// The logged in user's API tokens, to make new ones and revoke old ones.
+#[component]
+fn Tokens() -> impl IntoView {
+    let (message, set_message) = signal(None::<Result<String, String>>);
+    let (created, set_created) = signal(None::<String>);
+    let (version, set_version) = signal(0usize);
+    let (name, set_name) = signal(String::new());
+    let (write, set_write) = signal(false);
+    let (expires_in_days, set_expires_in_days) = signal(Some(90));
+    let tokens = Resource::new(move || version.get(), |_| list_tokens());
+    let done = Callback::new(move |result: Result<String, String>| {
+        set_message(Some(result));
+        set_version.update(|version| *version += 1);
+    });
+
+    let create = move |_| {
+        let name = name.get_untracked();
+        let scopes = if write.get_untracked() { Scope::ALL.to_vec() } else { vec![Scope::Read] };
+        spawn_local(async move {
+            let result = create_token(name.clone(), scopes, expires_in_days.get_untracked()).await;
+            if let Ok(token) = &result {
+                set_created(Some(token.clone()));
+                set_name(String::new());
+            }
+            done.run(result.map(|_| format!("Made {name}.")).map_err(|e| e.to_string()));
+        });
+    };
+    let revoke = move |id: i64, name: String| {
+        spawn_local(async move {
+            let result = revoke_token(id).await;
+            done.run(result.map(|_| format!("Revoked {name}.")).map_err(|e| e.to_string()));
+        });
+    };
+
+    let token_rows = move || match tokens.get() {
+        None => view! { <p>"Loading..."</p> }.into_any(),
+        Some(Err(e)) => view! { <p class="error">{e.to_string()}</p> }.into_any(),
+        Some(Ok(tokens)) if tokens.is_empty() => view! { <p>"You have no tokens."</p> }.into_any(),
+        Some(Ok(tokens)) => {
+            let rows = tokens
+                .into_iter()
+                .map(|token| {
+                    let (id, name) = (token.id, token.name.clone());
+                    let scopes = token.scopes.iter().map(Scope::as_str).collect::<Vec<_>>().join(", ");
+                    view! {
+                        <tr>
+                            <td class="cell-border">{token.name}</td>
+                            <td class="cell-border">{scopes}</td>
+                            <td class="cell-border">{token.created_at}</td>
+                            <td class="cell-border">{token.expires_at.unwrap_or_else(|| "never".to_string())}</td>
+                            <td class="cell-border">{token.last_used_at}</td>
+                            <td class="cell-border">
+                                <button class="generic-box" on:click=move |_| revoke(id, name.clone())>
+                                    "Revoke"
+                                </button>
+                            </td>
+                        </tr>
+                    }
+                })
+                .collect_view();
+            view! {
+                <table class="bordered-table">
+                    <thead>
+                        <tr>
+                            <th>"Name"</th>
+                            <th>"Scopes"</th>
+                            <th>"Made"</th>
+                            <th>"Expires"</th>
+                            <th>"Last used"</th>
+                            <th></th>
+                        </tr>
+                    </thead>
+                    <tbody>{rows}</tbody>
+                </table>
+            }
+            .into_any()
+        }
+    };
+
+    let message = move || match message.get() {
+        Some(Ok(message)) => view! { <p>{message}</p> }.into_any(),
+        Some(Err(e)) => view! { <p class="error">{e}</p> }.into_any(),
+        None => ().into_any(),
+    };
+    let created = move || created.get().map(|token| view! {
+        <p>"Copy the token now, it won't be shown again: " <code class="token">{token}</code></p>
+    });
+
+    view! {
+        <div class="admin-page">
+            <h2>"API tokens"</h2>
+            <p>"Scripts send a token as " <code>"Authorization: Bearer <token>"</code> "."</p>
+            {message}
+            {created}
+            <div class="admin-actions">
+                <input
+                    type="text"
+                    class="generic-box"
+                    placeholder="Name, e.g. my notebook"
+                    prop:value=name
+                    on:input=move |ev| set_name(event_target_value(&ev))
+                />
+                <label>
+                    <input
+                        type="checkbox"
+                        prop:checked=write
+                        on:change=move |ev| set_write(event_target_checked(&ev))
+                    />
+                    " Can make changes"
+                </label>
+                <select
+                    class="generic-box"
+                    on:change=move |ev| set_expires_in_days(event_target_value(&ev).parse().ok())
+                >
+                    <option value="30">"30 days"</option>
+                    <option value="90" selected>"90 days"</option>
+                    <option value="365">"A year"</option>
+                    <option value="">"Never expires"</option>
+                </select>
+                <button class="generic-box" on:click=create>"Make token"</button>
+            </div>
+            <Transition fallback=move || view! { <p>"Loading..."</p> }>{token_rows}</Transition>
+        </div>
+    }
+}
+
+/// The audit log, newest first, filtered by who, which screen, what and when. Only site admins
+/// can read it.
+#[component]
+fn AdminAudit() -> impl IntoView {
+    let (actor, set_actor) = signal(String::new());
+    let (screen, set_screen) = signal(String::new());
+    let (action, set_action) = signal(None::<AuditAction>);
+    let (from, set_from) = signal(String::new());
+    let (to, set_to) = signal(String::new());
+    // Only looked up again when the filter is applied, not on every keystroke.
+    let (filter, set_filter) = signal(AuditFilter::default());
+    let entries = Resource::new(move || filter.get(), list_audit);
+
+    let apply = move |ev: SubmitEvent| {
+        ev.prevent_default();
+        let non_empty = |s: String| Some(s).filter(|s| !s.trim().is_empty());
+        set_filter(AuditFilter {
+            actor: non_empty(actor.get_untracked()),
+            screen: non_empty(screen.get_untracked()),
+            action: action.get_untracked(),
+            from: non_empty(from.get_untracked()),
+            to: non_empty(to.get_untracked()),
+        });
+    };
+
+    let entry_rows = move || match entries.get() {
+        None => view! { <p>"Loading..."</p> }.into_any(),
+        Some(Err(e)) => view! { <p class="error">{e.to_string()}</p> }.into_any(),
+        Some(Ok(entries)) if entries.is_empty() => view! { <p>"No entries match."</p> }.into_any(),
+        Some(Ok(entries)) => {
+            let truncated = (entries.len() as i64 >= MAX_ENTRIES).then(|| view! {
+                <p>"Showing the newest " {MAX_ENTRIES} " entries, narrow the filter to see older ones."</p>
+            });
+            let rows = entries
+                .into_iter()
+                .map(|entry| view! {
+                    <tr>
+                        <td class="cell-border">{entry.at}</td>
+                        <td class="cell-border">{entry.actor}</td>
+                        <td class="cell-border">{entry.via}</td>
+                        <td class="cell-border">{entry.action}</td>
+                        <td class="cell-border">{entry.screen}</td>
+                        <td class="cell-border">{entry.detail}</td>
+                    </tr>
+                })
+                .collect_view();
+            view! {
+                {truncated}
+                <div class="scroll-container">
+                    <table class="bordered-table">
+                        <thead>
+                            <tr>
+                                <th>"When"</th>
+                                <th>"Who"</th>
+                                <th>"Via"</th>
+                                <th>"Action"</th>
+                                <th>"Screen"</th>
+                                <th>"Detail"</th>
+                            </tr>
+                        </thead>
+                        <tbody>{rows}</tbody>
+                    </table>
+                </div>
+            }
+            .into_any()
+        }
+    };
+
+    let action_options = AuditAction::ALL
+        .into_iter()
+        .map(|action| view! { <option value=action.as_str()>{action.as_str()}</option> })
+        .collect_view();
+
+    view! {
+        <div class="admin-page">
+            <h2>"Audit log"</h2>
+            <form class="admin-actions" on:submit=apply>
+                <input
+                    type="text"
+                    class="generic-box"
+                    placeholder="User"
+                    prop:value=actor
+                    on:input=move |ev| set_actor(event_target_value(&ev))
+                />
+                <input
+                    type="text"
+                    class="generic-box"
+                    placeholder="Screen"
+                    prop:value=screen
+                    on:input=move |ev| set_screen(event_target_value(&ev))
+                />
+                <select
+                    class="generic-box"
+                    on:change=move |ev| set_action(AuditAction::parse(&event_target_value(&ev)))
+                >
+                    <option value="">"Any action"</option>
+                    {action_options}
+                </select>
+                <label>
+                    "From "
+                    <input
+                        type="date"
+                        class="generic-box"
+                        prop:value=from
+                        on:input=move |ev| set_from(event_target_value(&ev))
+                    />
+                </label>
+                <label>
+                    "To "
+                    <input
+                        type="date"
+                        class="generic-box"
+                        prop:value=to
+                        on:input=move |ev| set_to(event_target_value(&ev))
+                    />
+                </label>
+                <button type="submit" class="generic-box">"Filter"</button>
+            </form>
+            <Transition fallback=move || view! { <p>"Loading..."</p> }>{entry_rows}</Transition>
+        </div>
+    }
+}
@@ -0,0 +1,94 @@
+//! The audit log of who imported, changed, deleted or exported which screen, which the server
+//! adds to alongside process_csv, and the admin page that lists it.
+use crate::{
+    error::AppError,
+    interface::{AuditEntry, AuditFilter},
+};
+use leptos::server;
+use server_fn::codec::Json;
+
+type ServerFnResult<T> = Result<T, AppError>;
+
+/// The most entries listed at once, newest first.
+pub const MAX_ENTRIES: i64 = 1000;
+
+cfg_if::cfg_if! {
+if #[cfg(feature = "ssr")] {
+    use crate::auth::{CurrentUser, require_admin};
+    use crate::interface::AuditAction;
+    use crate::server::AppState;
+    use ingest::audit::Actor;
+    use leptos::prelude::use_context;
+    use sqlx::{PgExecutor, Row};
+
+    /// Adds an entry by `current`, e.g. from an axum handler.
+    pub async fn record_as<'e>(
+        executor: impl PgExecutor<'e>,
+        current: &CurrentUser,
+        action: AuditAction,
+        screen: Option<&str>,
+        detail: Option<&str>,
+    ) -> ServerFnResult<()> {
+        ingest::audit::record(executor, &current.actor()?, action.as_str(), screen, detail).await?;
+        Ok(())
+    }
+
+    /// The logged in user as the audit log names it.
+    pub fn actor() -> ServerFnResult<Actor> {
+        use_context::<CurrentUser>().unwrap_or_default().actor()
+    }
+
+    /// Adds an entry by the logged in user. Pass the transaction making the change, so the entry
+    /// is only kept if the change is.
+    pub async fn record<'e>(
+        executor: impl PgExecutor<'e>,
+        action: AuditAction,
+        screen: Option<&str>,
+        detail: Option<&str>,
+    ) -> ServerFnResult<()> {
+        ingest::audit::record(executor, &actor()?, action.as_str(), screen, detail).await?;
+        Ok(())
+    }
+}}
+
+/// The newest entries of the audit log matching `filter`. Only site admins can read it.
+#[server(name = ListAudit, prefix = "/api", input = Json)]
+pub async fn list_audit(filter: AuditFilter) -> ServerFnResult<Vec<AuditEntry>> {
+    require_admin()?;
+    let state = AppState::from_cx()?;
+    let non_empty = |s: Option<String>| s.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    let rows = sqlx::query(
+        r#"
+        SELECT id, to_char(at, 'YYYY-MM-DD HH24:MI:SS') AS at, actor, via, action, screen, detail
+        FROM screenmap.audit_log
+        WHERE ($1::TEXT IS NULL OR actor = $1)
+            AND ($2::TEXT IS NULL OR screen = $2)
+            AND ($3::TEXT IS NULL OR action = $3)
+            AND ($4::DATE IS NULL OR at >= $4::DATE)
+            AND ($5::DATE IS NULL OR at < $5::DATE + 1)
+        ORDER BY id DESC
+        LIMIT $6
+        "#,
+    )
+    .bind(non_empty(filter.actor))
+    .bind(non_empty(filter.screen))
+    .bind(filter.action.map(|action| action.as_str()))
+    .bind(non_empty(filter.from))
+    .bind(non_empty(filter.to))
+    .bind(MAX_ENTRIES)
+    .fetch_all(state.pool.as_ref())
+    .await?;
+    let mut entries = vec![];
+    for row in rows {
+        entries.push(AuditEntry {
+            id: row.try_get("id")?,
+            at: row.try_get("at")?,
+            actor: row.try_get("actor")?,
+            via: row.try_get("via")?,
+            action: row.try_get("action")?,
+            screen: row.try_get("screen")?,
+            detail: row.try_get("detail")?,
+        });
+    }
+    Ok(entries)
+}
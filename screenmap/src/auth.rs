@@ -0,0 +1,280 @@
+//! Local accounts and the cookie sessions they log in with. Every request passes through
+//! `session_layer`, which gives server functions the logged in user through `current_user`, and
+//! also accepts the API tokens of `tokens` as bearer tokens.
+use crate::{error::AppError, interface::User};
+use leptos::server;
+
+type ServerFnResult<T> = Result<T, AppError>;
+
+cfg_if::cfg_if! {
+if #[cfg(feature = "ssr")] {
+    use crate::server::AppState;
+    use argon2::password_hash::rand_core::{OsRng, RngCore};
+    use argon2::password_hash::{self, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+    use argon2::Argon2;
+    use axum::extract::{Request, State};
+    use crate::interface::Scope;
+    use crate::tokens::token_user;
+    use axum::http::{HeaderValue, StatusCode, header::{AUTHORIZATION, SET_COOKIE}};
+    use axum::middleware::Next;
+    use axum::response::{IntoResponse, Response};
+    use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+    use ingest::audit::Actor;
+    use leptos::prelude::{expect_context, use_context};
+    use leptos_axum::ResponseOptions;
+    use log::error;
+    use sha2::{Digest, Sha256};
+    use sqlx::PgPool;
+    use std::sync::LazyLock;
+
+    const SESSION_COOKIE: &str = "screenmap_session";
+    const SESSION_DAYS: i64 = 30;
+
+    /// Checked against when the username is unknown, so a login takes as long whether the user
+    /// exists or not.
+    static DUMMY_HASH: LazyLock<String> =
+        LazyLock::new(|| hash_password(&new_token()).expect("Failed to hash the dummy password."));
+
+    /// The session cookie, which is only sent over https unless SCREENMAP_INSECURE_COOKIES is set
+    /// for a server run over plain http.
+    fn session_cookie(value: String, max_age: time::Duration) -> Cookie<'static> {
+        Cookie::build((SESSION_COOKIE, value))
+            .path("/")
+            .http_only(true)
+            .secure(std::env::var_os("SCREENMAP_INSECURE_COOKIES").is_none())
+            .same_site(SameSite::Lax)
+            .max_age(max_age)
+            .build()
+    }
+
+    /// The user whose session or API token came with the request, if any. The server function
+    /// and page handlers provide it as context.
+    #[derive(Clone, Debug, Default)]
+    pub struct CurrentUser {
+        pub user: Option<User>,
+        /// What the API token allows, None for sessions, which allow everything.
+        pub scopes: Option<Vec<Scope>>,
+    }
+
+    impl CurrentUser {
+        /// The user, unless it isn't logged in or its token lacks `scope`.
+        pub fn require(&self, scope: Scope) -> ServerFnResult<&User> {
+            let user = self.user.as_ref().ok_or(AppError::NotLoggedIn)?;
+            match &self.scopes {
+                Some(scopes) if !scopes.contains(&scope) => Err(AppError::MissingScope(scope)),
+                _ => Ok(user),
+            }
+        }
+
+        /// The user as the audit log names it.
+        pub fn actor(&self) -> ServerFnResult<Actor> {
+            let user = self.user.as_ref().ok_or(AppError::NotLoggedIn)?;
+            Ok(Actor {
+                user_id: Some(user.id),
+                name: user.username.clone(),
+                via: if self.scopes.is_some() { "api" } else { "web" }.to_string(),
+            })
+        }
+    }
+
+    pub fn hash_password(password: &str) -> Result<String, password_hash::Error> {
+        let salt = SaltString::generate(&mut OsRng);
+        Ok(Argon2::default().hash_password(password.as_bytes(), &salt)?.to_string())
+    }
+
+    /// Sessions and API tokens are stored by hash, so the database doesn't hold working ones.
+    pub(crate) fn token_hash(token: &str) -> String {
+        format!("{:x}", Sha256::digest(token.as_bytes()))
+    }
+
+    /// 32 random bytes in hex.
+    pub(crate) fn new_token() -> String {
+        let mut token = [0u8; 32];
+        OsRng.fill_bytes(&mut token);
+        token.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    /// Creates the user, or sets the password and role of the user if it exists.
+    pub async fn create_user(
+        pool: &PgPool,
+        username: &str,
+        password: &str,
+        is_admin: bool,
+    ) -> anyhow::Result<()> {
+        let password_hash = hash_password(password).map_err(|e| anyhow::anyhow!("{e}"))?;
+        sqlx::query(
+            r#"
+            INSERT INTO screenmap.users (username, password_hash, is_admin) VALUES ($1, $2, $3)
+            ON CONFLICT (username) DO UPDATE
+            SET password_hash = EXCLUDED.password_hash, is_admin = EXCLUDED.is_admin
+            "#,
+        )
+        .bind(username)
+        .bind(password_hash)
+        .bind(is_admin)
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn session_user(pool: &PgPool, token: &str) -> Result<Option<User>, sqlx::Error> {
+        sqlx::query_as::<_, (i64, String, bool)>(
+            r#"
+            SELECT u.id, u.username, u.is_admin
+            FROM screenmap.sessions s JOIN screenmap.users u ON u.id = s.user_id
+            WHERE s.token_hash = $1 AND s.expires_at > now()
+            "#,
+        )
+        .bind(token_hash(token))
+        .fetch_optional(pool)
+        .await
+        .map(|user| user.map(|(id, username, is_admin)| User { id, username, is_admin }))
+    }
+
+    /// Attaches the user of the bearer token or else the session cookie to the request as
+    /// `CurrentUser`. A bearer token that isn't valid is refused rather than ignored, so scripts
+    /// find out.
+    pub async fn session_layer(
+        State(state): State<AppState>,
+        jar: CookieJar,
+        mut request: Request,
+        next: Next,
+    ) -> Response {
+        let bearer = request
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(|token| token.trim().to_string());
+        let current = if let Some(token) = bearer {
+            match token_user(&state.pool, &token).await {
+                Ok(Some((user, scopes))) => CurrentUser { user: Some(user), scopes: Some(scopes) },
+                Ok(None) => {
+                    return (StatusCode::UNAUTHORIZED, "The API token is invalid or has expired.")
+                        .into_response();
+                }
+                Err(e) => {
+                    error!("Failed to look up an API token: {e}");
+                    return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+                }
+            }
+        } else {
+            let user = match jar.get(SESSION_COOKIE) {
+                Some(cookie) => session_user(&state.pool, cookie.value())
+                    .await
+                    .unwrap_or_else(|e| {
+                        error!("Failed to look up a session: {e}");
+                        None
+                    }),
+                None => None,
+            };
+            CurrentUser { user, scopes: None }
+        };
+        request.extensions_mut().insert(current);
+        next.run(request).await
+    }
+
+    pub fn current_user() -> Option<User> {
+        use_context::<CurrentUser>().and_then(|current| current.user)
+    }
+
+    /// The logged in user, unless its token lacks `scope`.
+    pub fn require_scope(scope: Scope) -> ServerFnResult<User> {
+        use_context::<CurrentUser>()
+            .unwrap_or_default()
+            .require(scope)
+            .cloned()
+    }
+
+    /// The logged in user, for reading screens.
+    pub fn require_user() -> ServerFnResult<User> {
+        require_scope(Scope::Read)
+    }
+
+    pub fn require_admin() -> ServerFnResult<User> {
+        let user = require_scope(Scope::Write)?;
+        if user.is_admin {
+            Ok(user)
+        } else {
+            Err(AppError::NotAdmin)
+        }
+    }
+
+    /// The user logged in with a session, as opposed to an API token, which can't make more.
+    pub fn require_session() -> ServerFnResult<User> {
+        let current = use_context::<CurrentUser>().unwrap_or_default();
+        if current.scopes.is_some() {
+            return Err(AppError::new("API tokens can't manage API tokens."));
+        }
+        current.user.ok_or(AppError::NotLoggedIn)
+    }
+
+    fn set_cookie(cookie: Cookie<'static>) -> ServerFnResult<()> {
+        let cookie = HeaderValue::from_str(&cookie.to_string()).map_err(AppError::new)?;
+        expect_context::<ResponseOptions>().append_header(SET_COOKIE, cookie);
+        Ok(())
+    }
+}}
+
+/// Starts a session for the user and goes to the screens.
+#[server(name = Login, prefix = "/api")]
+pub async fn login(username: String, password: String) -> ServerFnResult<()> {
+    let state = AppState::from_cx()?;
+    let user: Option<(i64, String)> =
+        sqlx::query_as("SELECT id, password_hash FROM screenmap.users WHERE username = $1")
+            .bind(username.trim())
+            .fetch_optional(state.pool.as_ref())
+            .await?;
+    let (user_id, password_hash) = match user {
+        Some((id, password_hash)) => (Some(id), password_hash),
+        None => (None, DUMMY_HASH.clone()),
+    };
+    let verified = PasswordHash::new(&password_hash).is_ok_and(|password_hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &password_hash)
+            .is_ok()
+    });
+    let user_id = user_id
+        .filter(|_| verified)
+        .ok_or_else(|| AppError::new("Wrong username or password."))?;
+
+    let token = new_token();
+    sqlx::query("DELETE FROM screenmap.sessions WHERE expires_at <= now()")
+        .execute(state.pool.as_ref())
+        .await?;
+    sqlx::query(
+        r#"
+        INSERT INTO screenmap.sessions (token_hash, user_id, expires_at)
+        VALUES ($1, $2, now() + make_interval(days => $3))
+        "#,
+    )
+    .bind(token_hash(&token))
+    .bind(user_id)
+    .bind(SESSION_DAYS as i32)
+    .execute(state.pool.as_ref())
+    .await?;
+    set_cookie(session_cookie(token, time::Duration::days(SESSION_DAYS)))?;
+    leptos_axum::redirect("/");
+    Ok(())
+}
+
+/// Ends the session and goes back to the login page.
+#[server(name = Logout, prefix = "/api")]
+pub async fn logout() -> ServerFnResult<()> {
+    let state = AppState::from_cx()?;
+    let jar: CookieJar = leptos_axum::extract().await?;
+    if let Some(cookie) = jar.get(SESSION_COOKIE) {
+        sqlx::query("DELETE FROM screenmap.sessions WHERE token_hash = $1")
+            .bind(token_hash(cookie.value()))
+            .execute(state.pool.as_ref())
+            .await?;
+    }
+    set_cookie(session_cookie(String::new(), time::Duration::ZERO))?;
+    leptos_axum::redirect("/login");
+    Ok(())
+}
+
+#[server(name = GetCurrentUser, prefix = "/api")]
+pub async fn get_current_user() -> ServerFnResult<Option<User>> {
+    Ok(current_user())
+}
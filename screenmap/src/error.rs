@@ -0,0 +1,97 @@
+//! The error server functions fail with. Refused access has variants of its own, so pages can
+//! tell it apart from things going wrong.
+use crate::interface::{Role, Scope};
+use serde::{Deserialize, Serialize};
+use server_fn::{
+    codec::JsonEncoding,
+    error::{FromServerFnError, ServerFnErrorErr},
+};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, thiserror::Error)]
+pub enum AppError {
+    #[error("Log in to see screens.")]
+    NotLoggedIn,
+    #[error("Only admins can do that.")]
+    NotAdmin,
+    #[error("The API token doesn't have the {0} scope.")]
+    MissingScope(Scope),
+    // the user's role on the screen or project is below `needed`, or it has none
+    #[error("You don't have {needed} access to {name}.")]
+    Forbidden { name: String, needed: Role },
+    #[error("There is no screen {0:?}.")]
+    NoSuchScreen(String),
+    #[error("There is no project {0:?}.")]
+    NoSuchProject(String),
+    #[error("There is no user {0:?}.")]
+    NoSuchUser(String),
+    // anything else, with a message that can be shown as it is
+    #[error("{0}")]
+    Failed(String),
+    #[error("{0}")]
+    ServerFn(ServerFnErrorErr),
+}
+
+impl AppError {
+    pub fn new(message: impl ToString) -> Self {
+        AppError::Failed(message.to_string())
+    }
+}
+
+impl FromServerFnError for AppError {
+    type Encoder = JsonEncoding;
+
+    fn from_server_fn_error(value: ServerFnErrorErr) -> Self {
+        AppError::ServerFn(value)
+    }
+}
+
+impl From<ServerFnErrorErr> for AppError {
+    fn from(value: ServerFnErrorErr) -> Self {
+        AppError::ServerFn(value)
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<sqlx::Error> for AppError {
+    fn from(value: sqlx::Error) -> Self {
+        AppError::new(value)
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<ingest::Error> for AppError {
+    fn from(value: ingest::Error) -> Self {
+        AppError::new(value)
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(value: std::io::Error) -> Self {
+        AppError::new(value)
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl AppError {
+    pub fn status(&self) -> axum::http::StatusCode {
+        use axum::http::StatusCode;
+        match self {
+            AppError::NotLoggedIn => StatusCode::UNAUTHORIZED,
+            AppError::NotAdmin | AppError::MissingScope(_) | AppError::Forbidden { .. } => {
+                StatusCode::FORBIDDEN
+            }
+            AppError::NoSuchScreen(_) | AppError::NoSuchProject(_) | AppError::NoSuchUser(_) => {
+                StatusCode::NOT_FOUND
+            }
+            AppError::Failed(_) | AppError::ServerFn(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// For axum handlers outside the server functions, such as the export.
+#[cfg(feature = "ssr")]
+impl axum::response::IntoResponse for AppError {
+    fn into_response(self) -> axum::response::Response {
+        (self.status(), self.to_string()).into_response()
+    }
+}
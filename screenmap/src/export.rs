@@ -0,0 +1,47 @@
+//! Whole screens as csv at /export/{screen}, for the download link above the tables and for
+//! scripts with an API token.
+use crate::audit;
+use crate::auth::CurrentUser;
+use crate::error::AppError;
+use crate::interface::{AuditAction, Role, Scope};
+use crate::projects::check_screen;
+use crate::server::{AppState, file_order, get_columns};
+use axum::body::Body;
+use axum::extract::{Extension, Path, State};
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::response::{IntoResponse, Response};
+use sqlx::postgres::PgPoolCopyExt;
+
+/// Streams every row of the screen in the order of its file, under a header row. Each export is
+/// in the audit log.
+pub async fn export_screen(
+    Path(screen): Path<String>,
+    Extension(current): Extension<CurrentUser>,
+    State(state): State<AppState>,
+) -> Result<Response, AppError> {
+    let user = current.require(Scope::Read)?;
+    check_screen(&state, user, &screen, Role::Viewer).await?;
+    let order_by = file_order(&get_columns(&screen, &state).await?).join(", ");
+    let rows = state
+        .pool
+        .copy_out_raw(&format!(
+            "COPY (SELECT * FROM {screen} ORDER BY {order_by}) TO STDOUT WITH (FORMAT csv, HEADER)"
+        ))
+        .await?;
+    audit::record_as(
+        state.pool.as_ref(),
+        &current,
+        AuditAction::Export,
+        Some(&screen),
+        None,
+    )
+    .await?;
+    let headers = [
+        (CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+        (
+            CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{screen}.csv\""),
+        ),
+    ];
+    Ok((headers, Body::from_stream(rows)).into_response())
+}
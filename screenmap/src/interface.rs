@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
 pub enum DataCell {
     Double(f64),
     BigInt(i64),
@@ -12,6 +14,20 @@ impl Default for DataCell {
     fn default() -> Self { return Self::Null }
 }
 
+/// UniProt annotation for the cysteine in a row, if the screen's identifiers could be parsed and
+/// the protein has been imported with `process_csv uniprot`.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Default)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+pub struct ProteinContext {
+    pub protein_name: Option<String>,
+    pub gene_name: Option<String>,
+    /// Residues around the cysteine, which is always at index `SEQUENCE_WINDOW`. Positions past
+    /// either end of the sequence are filled with '-'.
+    pub window: Option<String>,
+}
+
+pub const SEQUENCE_WINDOW: usize = 10;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CysQuery {
     pub cys_name: String,
@@ -19,6 +35,7 @@ pub struct CysQuery {
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
 pub enum ColType {
     SMALLINT = 0,
     INT = 1,
@@ -41,4 +58,394 @@ impl ColType {
             _ => None,
         }
     }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ColType::SMALLINT => "smallint",
+            ColType::INT => "integer",
+            ColType::BIGINT => "bigint",
+            ColType::REAL => "real",
+            ColType::DOUBLE => "double precision",
+            ColType::TEXT => "text",
+        }
+    }
+}
+
+/// A column of an uploaded file as process_csv would load it, which the upload page lets the
+/// admin rename and retype.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct UploadColumn {
+    pub header: String,
+    pub name: String,
+    pub col_type: ColType,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct UploadPreview {
+    /// Names the stored upload for the import that follows.
+    pub upload_id: String,
+    pub file_name: String,
+    pub table: String,
+    /// Whether a table of that name exists, so importing replaces it.
+    pub exists: bool,
+    pub columns: Vec<UploadColumn>,
+    pub sample: Vec<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl JobState {
+    pub fn parse(state: &str) -> Option<Self> {
+        match state {
+            "queued" => Some(JobState::Queued),
+            "running" => Some(JobState::Running),
+            "succeeded" => Some(JobState::Succeeded),
+            "failed" => Some(JobState::Failed),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobState::Queued => "queued",
+            JobState::Running => "running",
+            JobState::Succeeded => "succeeded",
+            JobState::Failed => "failed",
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobState::Succeeded | JobState::Failed)
+    }
+}
+
+/// An import started from the upload page, as the server sends it over /ws/jobs/{id} whenever
+/// it changes.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Job {
+    pub id: i64,
+    pub file_name: String,
+    pub table: String,
+    pub state: JobState,
+    pub rows_processed: i64,
+    pub error: Option<String>,
+}
+
+/// A screen as the admin page lists it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ScreenSummary {
+    pub name: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// Postgres's estimate, which can be off until the table is next analyzed.
+    pub num_rows: usize,
+    /// When it was last imported, None for screens loaded before imports were recorded.
+    pub imported_at: Option<String>,
+    pub archived: bool,
+    /// The project it belongs to, None for screens every user can see.
+    pub project: Option<String>,
+    /// The role the user listing it has on it.
+    pub role: Role,
+}
+
+/// How the admin page has set a screen to be shown.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct ScreenMeta {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// Names shown instead of column names, by column.
+    pub display_names: BTreeMap<String, String>,
+}
+
+/// A logged in user.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct User {
+    pub id: i64,
+    pub username: String,
+    pub is_admin: bool,
+}
+
+/// What a member can do with the screens of a project. Each role can do what the ones before it
+/// can: viewers see the screens, editors also describe them, and admins also rename, archive and
+/// delete them and manage the members.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Viewer,
+    Editor,
+    Admin,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Viewer, Role::Editor, Role::Admin];
+
+    pub fn parse(role: &str) -> Option<Self> {
+        match role {
+            "viewer" => Some(Role::Viewer),
+            "editor" => Some(Role::Editor),
+            "admin" => Some(Role::Admin),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A project as the projects page lists it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Project {
+    pub name: String,
+    /// Usernames with their roles.
+    pub members: Vec<(String, Role)>,
+    pub screens: Vec<String>,
+}
+
+/// What an API token may be used for. Browser sessions can do everything their user can.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// Searching, reading and exporting screens.
+    Read,
+    /// Everything else the user can do, e.g. editing screens and managing projects.
+    Write,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 2] = [Scope::Read, Scope::Write];
+
+    pub fn parse(scope: &str) -> Option<Self> {
+        match scope {
+            "read" => Some(Scope::Read),
+            "write" => Some(Scope::Write),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::Write => "write",
+        }
+    }
+}
+
+impl std::fmt::Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A personal API token as the tokens page lists it. The token itself is only shown once, when
+/// it is made.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ApiToken {
+    pub id: i64,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: String,
+    /// None for tokens that don't expire.
+    pub expires_at: Option<String>,
+    pub last_used_at: Option<String>,
+}
+
+/// What an audit log entry records. process_csv writes the first three itself, so their names
+/// are also in its source.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Import,
+    Replace,
+    Append,
+    Rename,
+    Archive,
+    Restore,
+    Delete,
+    Edit,
+    Move,
+    Export,
+    SetMember,
+    RemoveMember,
+}
+
+impl AuditAction {
+    pub const ALL: [AuditAction; 12] = [
+        AuditAction::Import,
+        AuditAction::Replace,
+        AuditAction::Append,
+        AuditAction::Rename,
+        AuditAction::Archive,
+        AuditAction::Restore,
+        AuditAction::Delete,
+        AuditAction::Edit,
+        AuditAction::Move,
+        AuditAction::Export,
+        AuditAction::SetMember,
+        AuditAction::RemoveMember,
+    ];
+
+    pub fn parse(action: &str) -> Option<Self> {
+        AuditAction::ALL
+            .into_iter()
+            .find(|known| known.as_str() == action)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Import => "import",
+            AuditAction::Replace => "replace",
+            AuditAction::Append => "append",
+            AuditAction::Rename => "rename",
+            AuditAction::Archive => "archive",
+            AuditAction::Restore => "restore",
+            AuditAction::Delete => "delete",
+            AuditAction::Edit => "edit",
+            AuditAction::Move => "move",
+            AuditAction::Export => "export",
+            AuditAction::SetMember => "set_member",
+            AuditAction::RemoveMember => "remove_member",
+        }
+    }
+}
+
+impl std::fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Which entries of the audit log to list. Unset fields match everything, and the dates are
+/// YYYY-MM-DD, both included.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct AuditFilter {
+    pub actor: Option<String>,
+    pub screen: Option<String>,
+    pub action: Option<AuditAction>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub at: String,
+    /// The username, or the account process_csv ran as.
+    pub actor: String,
+    /// "web", "api" or "process_csv".
+    pub via: String,
+    pub action: String,
+    pub screen: Option<String>,
+    pub detail: Option<String>,
+}
+
+/// A screen as /v1/screens lists it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+pub struct ScreenInfo {
+    pub name: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub num_rows: usize,
+    /// The project it belongs to, None for published screens.
+    pub project: Option<String>,
+    /// Archived screens are left out of the search on the website.
+    pub archived: bool,
+}
+
+/// A column of a screen as /v1/screens/{screen} describes it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+pub struct ColumnInfo {
+    pub name: String,
+    pub col_type: ColType,
+    /// The name the website shows for it, if it was given one.
+    pub display_name: Option<String>,
+    /// What process_csv recorded it as holding, e.g. key, site, ratio or intensity.
+    pub roles: Vec<String>,
+    /// The smallest and largest finite values of floating point columns.
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+pub struct ScreenDescription {
+    pub name: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// The column identifying a row, which cysteines are looked up by.
+    pub key_column: Option<String>,
+    pub num_rows: usize,
+    pub columns: Vec<ColumnInfo>,
+}
+
+/// A cell as /v1 sends it: a plain json number, string or null. Integer columns are sent without
+/// a fraction and floating point ones always with one, so each reads back as what it was.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+pub enum CellValue {
+    Int(i64),
+    Float(f64),
+    Text(String),
+    Null,
+}
+
+impl From<DataCell> for CellValue {
+    fn from(cell: DataCell) -> Self {
+        match cell {
+            DataCell::BigInt(value) => CellValue::Int(value),
+            DataCell::Double(value) => CellValue::Float(value),
+            DataCell::Text(value) => CellValue::Text(value),
+            DataCell::Null => CellValue::Null,
+        }
+    }
+}
+
+impl From<CellValue> for DataCell {
+    fn from(value: CellValue) -> Self {
+        match value {
+            CellValue::Int(value) => DataCell::BigInt(value),
+            CellValue::Float(value) => DataCell::Double(value),
+            CellValue::Text(value) => DataCell::Text(value),
+            CellValue::Null => DataCell::Null,
+        }
+    }
+}
+
+/// A row of a screen by column, with UniProt annotation for its cysteine.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+pub struct DataRow {
+    pub id: usize,
+    pub cells: BTreeMap<String, CellValue>,
+    pub protein: ProteinContext,
+}
+
+/// A page of the rows matching a query of /v1/screens/{screen}/rows.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+pub struct RowsPage {
+    /// How many rows match, on every page.
+    pub total: usize,
+    pub offset: usize,
+    pub rows: Vec<DataRow>,
 }
@@ -0,0 +1,369 @@
+//! Imports started from the upload page, run in the background one at a time. Their state is kept
+//! in screenmap.jobs and pushed to the page over /ws/jobs/{id} as it changes.
+use crate::auth::CurrentUser;
+use crate::interface::{AuditAction, Job, JobState, Scope, UploadColumn};
+use crate::server::AppState;
+use crate::upload::{expire_uploads, open_upload, record_type, remove_upload, uploads_dir};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Extension, Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use ingest::audit::{self, Actor};
+use ingest::catalog;
+use log::{error, info};
+use sqlx::{PgPool, Row, postgres::PgRow};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::{Notify, broadcast};
+
+/// How often the rows processed by a running job are written down and sent.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+const JOB_COLUMNS: &str = "id, file_name, table_name, state, rows_processed, error";
+
+#[derive(Clone)]
+pub struct Jobs {
+    pool: Arc<PgPool>,
+    queued: Arc<Notify>,
+    updates: broadcast::Sender<Job>,
+}
+
+/// What an admin asked to import from an upload.
+pub struct ImportRequest<'a> {
+    pub upload_id: &'a str,
+    pub file_name: &'a str,
+    pub table_name: &'a str,
+    pub columns: &'a [UploadColumn],
+    pub replace: bool,
+    /// The project to put the screen in. None leaves a replaced screen where it is and publishes
+    /// a new one.
+    pub project: Option<&'a str>,
+}
+
+/// A job as the worker takes it off the queue.
+struct QueuedJob {
+    id: i64,
+    upload_id: String,
+    file_name: String,
+    table_name: String,
+    columns: Vec<UploadColumn>,
+    replace: bool,
+    project: Option<String>,
+    queued_by: Actor,
+}
+
+fn job_from_row(row: PgRow) -> Result<Job, sqlx::Error> {
+    let state: String = row.try_get("state")?;
+    Ok(Job {
+        id: row.try_get("id")?,
+        file_name: row.try_get("file_name")?,
+        table: row.try_get("table_name")?,
+        state: JobState::parse(&state).ok_or_else(|| sqlx::Error::ColumnDecode {
+            index: "state".to_string(),
+            source: format!("unknown job state {state:?}").into(),
+        })?,
+        rows_processed: row.try_get("rows_processed")?,
+        error: row.try_get("error")?,
+    })
+}
+
+pub async fn fetch_job(pool: &PgPool, id: i64) -> Result<Option<Job>, sqlx::Error> {
+    sqlx::query(&format!(
+        "SELECT {JOB_COLUMNS} FROM screenmap.jobs WHERE id = $1"
+    ))
+    .bind(id)
+    .fetch_optional(pool)
+    .await?
+    .map(job_from_row)
+    .transpose()
+}
+
+impl Jobs {
+    /// Starts the worker, after failing the jobs a previous server left running and deleting
+    /// abandoned uploads.
+    pub async fn start(pool: Arc<PgPool>) -> Result<Self, sqlx::Error> {
+        let stopped: Vec<String> = sqlx::query_scalar(
+            r#"
+            UPDATE screenmap.jobs
+            SET state = 'failed', error = 'The server stopped during the import.', finished_at = now()
+            WHERE state = 'running'
+            RETURNING upload_id
+            "#,
+        )
+        .fetch_all(pool.as_ref())
+        .await?;
+        for upload_id in stopped {
+            remove_upload(&upload_id);
+        }
+        expire_uploads(&pool).await?;
+        let jobs = Jobs {
+            pool,
+            queued: Arc::new(Notify::new()),
+            updates: broadcast::channel(64).0,
+        };
+        tokio::spawn(jobs.clone().work());
+        Ok(jobs)
+    }
+
+    /// Queues the import of an upload and wakes the worker, unless the upload is already queued
+    /// or being imported. The import is audited as by `queued_by`.
+    pub async fn enqueue(
+        &self,
+        request: &ImportRequest<'_>,
+        queued_by: &Actor,
+    ) -> anyhow::Result<Job> {
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO screenmap.jobs
+                (upload_id, file_name, table_name, columns, replace, project, queued_by, queued_by_name, queued_via)
+            VALUES ($1, $2, $3, $4::TEXT::JSONB, $5, $6, $7, $8, $9)
+            RETURNING {JOB_COLUMNS}
+            "#
+        ))
+        .bind(request.upload_id)
+        .bind(request.file_name)
+        .bind(request.table_name)
+        .bind(serde_json::to_string(request.columns)?)
+        .bind(request.replace)
+        .bind(request.project)
+        .bind(queued_by.user_id)
+        .bind(&queued_by.name)
+        .bind(&queued_by.via)
+        .fetch_one(self.pool.as_ref())
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(e) if e.constraint() == Some("jobs_active_upload_idx") => {
+                anyhow::anyhow!("This upload is already being imported.")
+            }
+            e => e.into(),
+        })?;
+        self.queued.notify_one();
+        Ok(job_from_row(row)?)
+    }
+
+    async fn work(self) {
+        let pool = self.pool.clone();
+        loop {
+            match next_job(&pool).await {
+                Ok(Some(job)) => self.run(&pool, job).await,
+                // A job queued since the last look leaves a permit, so it isn't missed.
+                Ok(None) => self.queued.notified().await,
+                Err(e) => {
+                    error!("Failed to take the next job: {e}");
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }
+            }
+        }
+    }
+
+    async fn run(&self, pool: &Arc<PgPool>, job: QueuedJob) {
+        info!(
+            "Running job {} importing {} into {}",
+            job.id, job.file_name, job.table_name
+        );
+        let id = job.id;
+        let upload_id = job.upload_id.clone();
+        let progress = Arc::new(AtomicU64::new(0));
+        // Spawned so the import's long stretches without an await don't hold up the progress.
+        let mut import = tokio::spawn(import(pool.clone(), job, progress.clone()));
+        let mut ticks = tokio::time::interval(PROGRESS_INTERVAL);
+        let mut rows_sent = 0;
+        let result = loop {
+            tokio::select! {
+                result = &mut import => break result,
+                _ = ticks.tick() => {
+                    let rows = progress.load(Ordering::Relaxed);
+                    if rows == rows_sent {
+                        continue;
+                    }
+                    rows_sent = rows;
+                    let update = sqlx::query(&format!(
+                        "UPDATE screenmap.jobs SET rows_processed = $2 WHERE id = $1 RETURNING {JOB_COLUMNS}"
+                    ))
+                    .bind(id)
+                    .bind(rows as i64)
+                    .fetch_one(pool.as_ref())
+                    .await;
+                    self.send(update);
+                }
+            }
+        };
+        let (state, error) = match result {
+            Ok(Ok(())) => (JobState::Succeeded, None),
+            Ok(Err(e)) => {
+                error!("Job {id} failed: {e}");
+                (JobState::Failed, Some(e.to_string()))
+            }
+            Err(e) => {
+                error!("Job {id} panicked: {e}");
+                (JobState::Failed, Some("The import crashed.".to_string()))
+            }
+        };
+        // Removed before the job is finished, so it can't take the file of a job queued after.
+        remove_upload(&upload_id);
+        let update = sqlx::query(&format!(
+            r#"
+            UPDATE screenmap.jobs SET state = $2, error = $3, rows_processed = $4, finished_at = now()
+            WHERE id = $1
+            RETURNING {JOB_COLUMNS}
+            "#
+        ))
+        .bind(id)
+        .bind(state.as_str())
+        .bind(error)
+        .bind(progress.load(Ordering::Relaxed) as i64)
+        .fetch_one(pool.as_ref())
+        .await;
+        self.send(update);
+    }
+
+    fn send(&self, update: Result<PgRow, sqlx::Error>) {
+        match update.and_then(job_from_row) {
+            // Nobody may be watching, which is fine.
+            Ok(job) => _ = self.updates.send(job),
+            Err(e) => error!("Failed to update a job: {e}"),
+        }
+    }
+}
+
+/// Marks the oldest queued job running and returns it.
+async fn next_job(pool: &PgPool) -> anyhow::Result<Option<QueuedJob>> {
+    let Some(row) = sqlx::query(
+        r#"
+        UPDATE screenmap.jobs SET state = 'running', started_at = now()
+        WHERE id = (
+            SELECT id FROM screenmap.jobs WHERE state = 'queued' ORDER BY id LIMIT 1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING id, upload_id, file_name, table_name, columns::TEXT, replace, project,
+            queued_by, queued_by_name, queued_via
+        "#,
+    )
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Ok(None);
+    };
+    let columns: String = row.try_get("columns")?;
+    Ok(Some(QueuedJob {
+        id: row.try_get("id")?,
+        upload_id: row.try_get("upload_id")?,
+        file_name: row.try_get("file_name")?,
+        table_name: row.try_get("table_name")?,
+        columns: serde_json::from_str(&columns)?,
+        replace: row.try_get("replace")?,
+        project: row.try_get("project")?,
+        queued_by: Actor {
+            user_id: row.try_get("queued_by")?,
+            name: row
+                .try_get::<Option<String>, _>("queued_by_name")?
+                .unwrap_or_else(|| "unknown".to_string()),
+            via: row
+                .try_get::<Option<String>, _>("queued_via")?
+                .unwrap_or_else(|| "web".to_string()),
+        },
+    }))
+}
+
+/// Imports the upload with the columns as the admin edited them, into the job's project if it
+/// has one. An existing table is only replaced with `replace`.
+async fn import(pool: Arc<PgPool>, job: QueuedJob, progress: Arc<AtomicU64>) -> anyhow::Result<()> {
+    let pool = pool.as_ref();
+    let path = uploads_dir().join(&job.upload_id).join(&job.file_name);
+    let checksum = catalog::checksum(&path)?;
+    let project_id = match job.project.as_deref() {
+        Some(project) => Some(catalog::project_id(pool, project).await?),
+        None => None,
+    };
+    let (mut processor, preamble) = open_upload(pool, path, Some(&job.table_name)).await?;
+    let columns: Vec<_> = job
+        .columns
+        .iter()
+        .map(|column| (column.name.clone(), record_type(column.col_type)))
+        .collect();
+    processor.override_columns(&columns)?;
+    processor.report_progress(progress);
+    // The admin has seen the columns, so a replaced table may lose or narrow some.
+    let mut tx = pool.begin().await?;
+    let replaced = processor
+        .write_db(&mut tx, job.replace, job.replace)
+        .await?;
+    let action = if replaced {
+        AuditAction::Replace
+    } else {
+        AuditAction::Import
+    };
+    processor
+        .record(&mut tx, &job.file_name, &checksum, &preamble)
+        .await?;
+    let mut detail = job.file_name.clone();
+    if let (Some(project), Some(project_id)) = (job.project.as_deref(), project_id) {
+        catalog::set_project(&mut tx, &job.table_name, project_id).await?;
+        detail.push_str(&format!(", into {project}"));
+    }
+    audit::record(
+        &mut *tx,
+        &job.queued_by,
+        action.as_str(),
+        Some(&job.table_name),
+        Some(&detail),
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Sends the job with `id` as it is, then every change to it until it is finished. Only admins,
+/// who start jobs, can follow them.
+pub async fn job_socket(
+    ws: WebSocketUpgrade,
+    Path(id): Path<i64>,
+    Extension(current): Extension<CurrentUser>,
+    State(state): State<AppState>,
+) -> Response {
+    if !current.require(Scope::Read).is_ok_and(|user| user.is_admin) {
+        return (StatusCode::FORBIDDEN, "Only admins can follow imports.").into_response();
+    }
+    ws.on_upgrade(move |socket| send_updates(socket, state, id))
+}
+
+async fn send_updates(mut socket: WebSocket, state: AppState, id: i64) {
+    // Subscribed before the job is read, so no change is missed in between.
+    let mut updates = state.jobs.updates.subscribe();
+    let mut job = match fetch_job(&state.pool, id).await {
+        Ok(Some(job)) => job,
+        Ok(None) => return,
+        Err(e) => {
+            error!("Failed to read job {id}: {e}");
+            return;
+        }
+    };
+    loop {
+        let Ok(message) = serde_json::to_string(&job) else {
+            return;
+        };
+        if socket.send(Message::Text(message.into())).await.is_err() {
+            return;
+        }
+        if job.state.is_finished() {
+            // The page closes the socket once it has the result, rather than reconnecting.
+            while let Some(Ok(_)) = socket.recv().await {}
+            return;
+        }
+        job = loop {
+            match updates.recv().await {
+                Ok(update) if update.id == id => break update,
+                Ok(_) => {}
+                // Too far behind, so start over from the job as it is now.
+                Err(broadcast::error::RecvError::Lagged(_)) => {
+                    match fetch_job(&state.pool, id).await {
+                        Ok(Some(job)) => break job,
+                        _ => return,
+                    }
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+        };
+    }
+}
@@ -1,7 +1,20 @@
 #![feature(iterator_try_collect)]
+pub mod admin;
+#[cfg(feature = "ssr")]
+pub mod api;
 pub mod app;
+pub mod audit;
+pub mod auth;
+pub mod error;
+#[cfg(feature = "ssr")]
+pub mod export;
 pub mod interface;
+#[cfg(feature = "ssr")]
+pub mod jobs;
+pub mod projects;
 pub mod server;
+pub mod tokens;
+pub mod upload;
 
 #[cfg(feature = "hydrate")]
 #[wasm_bindgen::prelude::wasm_bindgen]
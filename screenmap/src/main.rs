@@ -5,12 +5,16 @@ async fn main() {
     use axum::extract::{Path, RawQuery, State};
     use axum::http::HeaderMap;
     use axum::response::{IntoResponse, Response};
-    use axum::{Router, http::Request, routing::get};
+    use axum::{Router, http::Request, middleware, routing::get};
     use leptos::prelude::{get_configuration, provide_context};
-    use leptos_axum::{LeptosRoutes, generate_route_list};
+    use leptos_axum::{AxumRouteListing, LeptosRoutes, generate_route_list};
     use log::info;
     use screenmap::{
+        api,
         app::{App, shell},
+        auth::{CurrentUser, create_user, session_layer},
+        export::export_screen,
+        jobs::{Jobs, job_socket},
         server::AppState,
     };
     use sqlx::postgres::PgPoolOptions;
@@ -24,37 +28,100 @@ async fn main() {
         request: Request<Body>,
     ) -> impl IntoResponse {
         info!("REQUEST {:?}", path);
-        leptos_axum::handle_server_fns_with_context(move || provide_context(state.clone()), request)
-            .await
+        let user = request.extensions().get::<CurrentUser>().cloned().unwrap_or_default();
+        leptos_axum::handle_server_fns_with_context(
+            move || {
+                provide_context(state.clone());
+                provide_context(user.clone());
+            },
+            request,
+        )
+        .await
     }
 
-    async fn leptos_routes_handler(State(state): State<AppState>, req: Request<Body>) -> Response {
+    // Renders with the SsrMode of the route, so protected pages can redirect before streaming.
+    async fn leptos_routes_handler(
+        State(state): State<AppState>,
+        routes: Vec<AxumRouteListing>,
+        req: Request<Body>,
+    ) -> Response {
         let new_state = state.clone();
-        let handler = leptos_axum::render_app_to_stream_with_context(
-            move || provide_context(new_state.clone()),
-            move || shell(state.leptos_options.clone()),
+        let options = state.leptos_options.clone();
+        let user = req.extensions().get::<CurrentUser>().cloned().unwrap_or_default();
+        let handler = leptos_axum::render_route_with_context(
+            routes,
+            move || {
+                provide_context(new_state.clone());
+                provide_context(user.clone());
+            },
+            move || shell(options.clone()),
         );
-        handler(req).await.into_response()
+        handler(State(state), req).await.into_response()
+    }
+
+    let postgres_url = std::env::var("DATABASE_URL").expect("DATABASE_URL is unset.");
+    let pool = Arc::new(PgPoolOptions::new().connect(&postgres_url).await.unwrap());
+    sqlx::raw_sql(include_str!("../../schema.sql"))
+        .execute(pool.as_ref())
+        .await
+        .expect("Failed to create screenmap schema.");
+
+    // `screenmap create-admin <username>` and `screenmap create-user <username>` read the password
+    // from stdin, and reset it if the user exists.
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(command @ ("create-admin" | "create-user")) = args.get(1).map(String::as_str) {
+        let Some(username) = args.get(2).map(|username| username.trim()).filter(|u| !u.is_empty()) else {
+            eprintln!("Usage: screenmap {command} <username>");
+            std::process::exit(2);
+        };
+        eprint!("Password for {username}: ");
+        let mut password = String::new();
+        std::io::stdin()
+            .read_line(&mut password)
+            .expect("Failed to read the password.");
+        let password = password.trim_end_matches(['\r', '\n']);
+        if password.is_empty() {
+            eprintln!("ERROR: The password is empty.");
+            std::process::exit(1);
+        }
+        match create_user(&pool, username, password, command == "create-admin").await {
+            Ok(()) => eprintln!("INFO: Saved user {username}."),
+            Err(e) => {
+                eprintln!("ERROR: Failed to save user {username}: {e}");
+                std::process::exit(1);
+            }
+        }
+        return;
     }
 
     let conf = get_configuration(None).unwrap();
     let addr = conf.leptos_options.site_addr;
     let leptos_options = conf.leptos_options;
-    let postgres_url = std::env::var("DATABASE_URL").expect("DATABASE_URL is unset.");
-    let pool = Arc::new(PgPoolOptions::new().connect(&postgres_url).await.unwrap());
+    let jobs = Jobs::start(pool.clone())
+        .await
+        .expect("Failed to start the job worker.");
     let state = AppState {
         pool,
         leptos_options,
+        jobs,
     };
     let routes = generate_route_list(App);
+    let page_routes = routes.clone();
 
     let app: Router = Router::new()
         .route(
             "/api/{*fn_name}",
             get(server_fn_handler).post(server_fn_handler),
         )
-        .leptos_routes_with_handler(routes, get(leptos_routes_handler))
+        .route("/ws/jobs/{id}", get(job_socket))
+        .route("/export/{screen}", get(export_screen))
+        .merge(api::router())
+        .leptos_routes_with_handler(
+            routes,
+            get(move |state, req| leptos_routes_handler(state, page_routes.clone(), req)),
+        )
         .fallback(leptos_axum::file_and_error_handler::<AppState, _>(shell))
+        .layer(middleware::from_fn_with_state(state.clone(), session_layer))
         .with_state(state);
 
     info!("listening on http://{}", &addr);
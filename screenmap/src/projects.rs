@@ -0,0 +1,251 @@
+//! Who can see and change which screens. Screens outside any project are published, which every
+//! user can view. The screens of a project, which imports can put them in directly, can only be
+//! seen by its members, with the role they were given. Site admins have every role everywhere.
+use crate::{
+    error::AppError,
+    interface::{Project, Role},
+};
+use leptos::server;
+
+type ServerFnResult<T> = Result<T, AppError>;
+
+cfg_if::cfg_if! {
+if #[cfg(feature = "ssr")] {
+    use crate::audit;
+    use crate::auth::{require_admin, require_scope, require_user};
+    use crate::interface::{AuditAction, Scope, User};
+    use crate::server::{AppState, get_tbls};
+    use std::collections::BTreeMap;
+
+    /// The role the user has on each screen it can see.
+    pub async fn screen_roles(state: &AppState, user: &User) -> ServerFnResult<BTreeMap<String, Role>> {
+        // Screens in a project, with the user's role in it if it is a member.
+        let in_projects: BTreeMap<String, Option<String>> = sqlx::query_as::<_, (String, Option<String>)>(
+            r#"
+            SELECT s.name, m.role
+            FROM screenmap.screens s
+            LEFT JOIN screenmap.project_members m ON m.project_id = s.project_id AND m.user_id = $1
+            WHERE s.project_id IS NOT NULL
+            "#,
+        )
+        .bind(user.id)
+        .fetch_all(state.pool.as_ref())
+        .await?
+        .into_iter()
+        .collect();
+        Ok(get_tbls(state)
+            .await?
+            .into_iter()
+            .filter_map(|name| {
+                let role = if user.is_admin {
+                    Role::Admin
+                } else {
+                    match in_projects.get(&name) {
+                        Some(role) => Role::parse(role.as_deref()?)?,
+                        None => Role::Viewer,
+                    }
+                };
+                Some((name, role))
+            })
+            .collect())
+    }
+
+    /// Fails unless `screen` exists and the logged in user has at least the `needed` role on it.
+    /// API tokens need the read scope to view it and the write scope for anything more.
+    pub async fn require_screen(state: &AppState, screen: &str, needed: Role) -> ServerFnResult<User> {
+        let scope = if needed == Role::Viewer { Scope::Read } else { Scope::Write };
+        let user = require_scope(scope)?;
+        check_screen(state, &user, screen, needed).await?;
+        Ok(user)
+    }
+
+    /// Fails unless `screen` exists and `user` has at least the `needed` role on it.
+    pub async fn check_screen(state: &AppState, user: &User, screen: &str, needed: Role) -> ServerFnResult<()> {
+        match screen_roles(state, user).await?.get(screen) {
+            Some(role) if *role >= needed => Ok(()),
+            Some(_) => Err(AppError::Forbidden { name: screen.to_string(), needed }),
+            None if get_tbls(state).await?.iter().any(|tbl| tbl == screen) => {
+                Err(AppError::Forbidden { name: screen.to_string(), needed })
+            }
+            None => Err(AppError::NoSuchScreen(screen.to_string())),
+        }
+    }
+
+    /// Fails unless the logged in user is an admin of `project`, and returns its id.
+    async fn require_project_admin(state: &AppState, project: &str) -> ServerFnResult<i64> {
+        let user = require_scope(Scope::Write)?;
+        let (id, role): (i64, Option<String>) = sqlx::query_as(
+            r#"
+            SELECT p.id, m.role
+            FROM screenmap.projects p
+            LEFT JOIN screenmap.project_members m ON m.project_id = p.id AND m.user_id = $2
+            WHERE p.name = $1
+            "#,
+        )
+        .bind(project)
+        .bind(user.id)
+        .fetch_optional(state.pool.as_ref())
+        .await?
+        .ok_or_else(|| AppError::NoSuchProject(project.to_string()))?;
+        if user.is_admin || role.as_deref().and_then(Role::parse) == Some(Role::Admin) {
+            Ok(id)
+        } else {
+            Err(AppError::Forbidden { name: project.to_string(), needed: Role::Admin })
+        }
+    }
+
+    pub async fn project_id(state: &AppState, project: &str) -> ServerFnResult<i64> {
+        sqlx::query_scalar("SELECT id FROM screenmap.projects WHERE name = $1")
+            .bind(project)
+            .fetch_optional(state.pool.as_ref())
+            .await?
+            .ok_or_else(|| AppError::NoSuchProject(project.to_string()))
+    }
+}}
+
+/// The projects the user manages with their members and screens, every project for site admins.
+#[server(name = ListProjects, prefix = "/api")]
+pub async fn list_projects() -> ServerFnResult<Vec<Project>> {
+    let user = require_user()?;
+    let state = AppState::from_cx()?;
+    let projects: Vec<(i64, String)> = sqlx::query_as(
+        r#"
+        SELECT p.id, p.name
+        FROM screenmap.projects p
+        WHERE $2 OR EXISTS (
+            SELECT 1 FROM screenmap.project_members m
+            WHERE m.project_id = p.id AND m.user_id = $1 AND m.role = 'admin'
+        )
+        ORDER BY p.name
+        "#,
+    )
+    .bind(user.id)
+    .bind(user.is_admin)
+    .fetch_all(state.pool.as_ref())
+    .await?;
+    let mut listed = vec![];
+    for (id, name) in projects {
+        let members: Vec<(String, String)> = sqlx::query_as(
+            r#"
+            SELECT u.username, m.role
+            FROM screenmap.project_members m JOIN screenmap.users u ON u.id = m.user_id
+            WHERE m.project_id = $1
+            ORDER BY u.username
+            "#,
+        )
+        .bind(id)
+        .fetch_all(state.pool.as_ref())
+        .await?;
+        let screens = sqlx::query_scalar(
+            "SELECT name FROM screenmap.screens WHERE project_id = $1 ORDER BY name",
+        )
+        .bind(id)
+        .fetch_all(state.pool.as_ref())
+        .await?;
+        listed.push(Project {
+            name,
+            members: members
+                .into_iter()
+                .filter_map(|(username, role)| Some((username, Role::parse(&role)?)))
+                .collect(),
+            screens,
+        });
+    }
+    Ok(listed)
+}
+
+#[server(name = CreateProject, prefix = "/api")]
+pub async fn create_project(name: String) -> ServerFnResult<()> {
+    require_admin()?;
+    let state = AppState::from_cx()?;
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::new("Give the project a name."));
+    }
+    let created =
+        sqlx::query("INSERT INTO screenmap.projects (name) VALUES ($1) ON CONFLICT DO NOTHING")
+            .bind(name)
+            .execute(state.pool.as_ref())
+            .await?;
+    if created.rows_affected() == 0 {
+        return Err(AppError::new(format!(
+            "There already is a project {name:?}."
+        )));
+    }
+    Ok(())
+}
+
+/// Deletes the project, which must have no screens left, so none are published by accident.
+#[server(name = DeleteProject, prefix = "/api")]
+pub async fn delete_project(name: String) -> ServerFnResult<()> {
+    require_admin()?;
+    let state = AppState::from_cx()?;
+    let id = project_id(&state, &name).await?;
+    let screens: i64 =
+        sqlx::query_scalar("SELECT count(*) FROM screenmap.screens WHERE project_id = $1")
+            .bind(id)
+            .fetch_one(state.pool.as_ref())
+            .await?;
+    if screens > 0 {
+        return Err(AppError::new(
+            "Move the screens out of the project before deleting it.",
+        ));
+    }
+    sqlx::query("DELETE FROM screenmap.projects WHERE id = $1")
+        .bind(id)
+        .execute(state.pool.as_ref())
+        .await?;
+    Ok(())
+}
+
+/// Adds the user to the project with `role`, or changes its role if it is a member.
+#[server(name = SetMember, prefix = "/api")]
+pub async fn set_member(project: String, username: String, role: Role) -> ServerFnResult<()> {
+    let state = AppState::from_cx()?;
+    let id = require_project_admin(&state, &project).await?;
+    let username = username.trim();
+    let user_id: i64 = sqlx::query_scalar("SELECT id FROM screenmap.users WHERE username = $1")
+        .bind(username)
+        .fetch_optional(state.pool.as_ref())
+        .await?
+        .ok_or_else(|| AppError::NoSuchUser(username.to_string()))?;
+    let mut tx = state.pool.begin().await?;
+    sqlx::query(
+        r#"
+        INSERT INTO screenmap.project_members (project_id, user_id, role) VALUES ($1, $2, $3)
+        ON CONFLICT (project_id, user_id) DO UPDATE SET role = EXCLUDED.role
+        "#,
+    )
+    .bind(id)
+    .bind(user_id)
+    .bind(role.as_str())
+    .execute(&mut *tx)
+    .await?;
+    let detail = format!("{username} is {role} of {project}");
+    audit::record(&mut *tx, AuditAction::SetMember, None, Some(&detail)).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+#[server(name = RemoveMember, prefix = "/api")]
+pub async fn remove_member(project: String, username: String) -> ServerFnResult<()> {
+    let state = AppState::from_cx()?;
+    let id = require_project_admin(&state, &project).await?;
+    let mut tx = state.pool.begin().await?;
+    let removed = sqlx::query(
+        r#"
+        DELETE FROM screenmap.project_members
+        WHERE project_id = $1 AND user_id = (SELECT id FROM screenmap.users WHERE username = $2)
+        "#,
+    )
+    .bind(id)
+    .bind(&username)
+    .execute(&mut *tx)
+    .await?;
+    if removed.rows_affected() > 0 {
+        let detail = format!("{username} from {project}");
+        audit::record(&mut *tx, AuditAction::RemoveMember, None, Some(&detail)).await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
@@ -1,17 +1,22 @@
-use crate::interface::{ColType, CysQuery, DataCell};
-use leptos::{prelude::ServerFnError, server};
+use crate::error::AppError;
+use crate::interface::{ColType, CysQuery, DataCell, ProteinContext, ScreenMeta};
+use leptos::server;
 use std::{collections::BTreeMap, fmt::Debug, ops::RangeInclusive};
 
-type ServerFnResult<T> = Result<T, ServerFnError>;
+type ServerFnResult<T> = Result<T, AppError>;
 
 cfg_if::cfg_if! {
 if #[cfg(feature = "ssr")] {
-    use anyhow::{Context, Error};
+    use anyhow::Error;
     use axum::extract::FromRef;
     use leptos::{
         config::LeptosOptions,
         prelude::{use_context},
     };
+    use crate::auth::require_user;
+    use crate::interface::{Role, SEQUENCE_WINDOW};
+    use crate::jobs::Jobs;
+    use crate::projects::{require_screen, screen_roles};
     use sqlx::{PgPool, Row, postgres::PgRow};
     use std::sync::Arc;
 
@@ -19,57 +24,48 @@ if #[cfg(feature = "ssr")] {
     pub struct AppState {
         pub leptos_options: LeptosOptions,
         pub pool: Arc<PgPool>,
+        pub jobs: Jobs,
     }
 
     impl AppState {
         pub fn from_cx() -> ServerFnResult<Self> {
-            use_context().ok_or(ServerFnError::new("Failed to provide context"))
+            use_context().ok_or(AppError::new("Failed to provide context"))
         }
     }
+
 }}
 
 #[server(name = CysLocation, prefix = "/api")]
 pub async fn cys_location(cys_query: CysQuery) -> ServerFnResult<i64> {
     let state = AppState::from_cx()?;
+    require_screen(&state, &cys_query.screen_name, Role::Viewer).await?;
     let get_result = async move || {
-        let cys_col_name = sqlx::query(
-            r#"
-        SELECT column_name 
-        FROM information_schema.columns 
-        WHERE table_name = $1
-        ORDER BY ordinal_position
-        "#,
-        )
-        .bind(&cys_query.screen_name)
-        .try_map(|row: sqlx::postgres::PgRow| row.try_get::<String, _>("column_name"))
-        .fetch_all(state.pool.as_ref())
-        .await?;
-
-        sqlx::query("SELECT id FROM $1 WHERE $2 = $3")
-            .bind(&cys_query.screen_name)
-            .bind(&cys_col_name[0])
+        let key_col = key_column(&cys_query.screen_name, &state).await?;
+        sqlx::query_scalar::<_, i32>(&format!("SELECT id FROM {} WHERE {}::TEXT = $1", cys_query.screen_name, key_col))
             .bind(&cys_query.cys_name)
             .fetch_optional(state.pool.as_ref())
             .await?
-            .map(|row| {
-                row.try_get::<i64, &str>("id")
-                    .context("Failed getting 'id' from table.")
-            })
+            .map(i64::from)
             .ok_or(Error::msg(
                 "ERROR: Given cysteine name not found in database.",
-            ))?
+            ))
     };
-    get_result().await.map_err(ServerFnError::new)
+    get_result().await.map_err(AppError::new)
 }
 
 #[server(name = Search, prefix = "/api")]
 pub async fn search_tbls(query: String) -> ServerFnResult<Vec<String>> {
     use fuzzy_matcher::{FuzzyMatcher, skim::SkimMatcherV2};
+    let user = require_user()?;
     let state = AppState::from_cx()?;
-    let tbls = get_tbls(&state).await?;
+    let tbls = screen_roles(&state, &user).await?.into_keys();
+    let archived: Vec<String> =
+        sqlx::query_scalar("SELECT name FROM screenmap.screens WHERE archived_at IS NOT NULL")
+            .fetch_all(state.pool.as_ref())
+            .await?;
     let matcher = SkimMatcherV2::default();
     let mut scored_tbls: Vec<_> = tbls
-        .into_iter()
+        .filter(|s| !archived.contains(s))
         .filter_map(|s| matcher.fuzzy_match(&s, &query).map(|x| (s, x)))
         .collect();
     scored_tbls.sort_by(|(_, x0), (_, x1)| x0.cmp(x1));
@@ -79,9 +75,13 @@ pub async fn search_tbls(query: String) -> ServerFnResult<Vec<String>> {
 #[server(name = GetNumRows, prefix = "/api")]
 pub async fn get_num_rows(tbl_name: String) -> ServerFnResult<usize> {
     let state = AppState::from_cx()?;
-    if !get_tbls(&state).await?.contains(&tbl_name) {
-        return Err(ServerFnError::new("Nice try."));
-    }
+    require_screen(&state, &tbl_name, Role::Viewer).await?;
+    count_rows(&state, &tbl_name).await
+}
+
+/// Counts the rows of a screen, which the caller has checked exists.
+#[cfg(feature = "ssr")]
+pub(crate) async fn count_rows(state: &AppState, tbl_name: &str) -> ServerFnResult<usize> {
     let count: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {}", tbl_name))
         .fetch_one(&*state.pool)
         .await?;
@@ -92,36 +92,48 @@ pub async fn get_num_rows(tbl_name: String) -> ServerFnResult<usize> {
 pub async fn search_table(
     tbl_name: String,
     query: String,
-) -> Result<Vec<RangeInclusive<usize>>, ServerFnError> {
+) -> ServerFnResult<Vec<RangeInclusive<usize>>> {
     let state = AppState::from_cx()?;
-    if !get_tbls(&state).await?.contains(&tbl_name) {
-        return Err(ServerFnError::new("Nice try."));
-    } else if query.is_empty() {
-        return Ok(vec![1..=get_num_rows(tbl_name).await?]);
+    require_screen(&state, &tbl_name, Role::Viewer).await?;
+    if query.is_empty() {
+        return Ok(vec![1..=count_rows(&state, &tbl_name).await?]);
     }
-    let columns = get_screen_keys(tbl_name.clone()).await?;
+    // Text columns are matched with ILIKE, which process_csv's trigram indexes serve. Numeric
+    // columns only match a query that parses as the same number, so their btree indexes apply.
+    // The columns process_csv adds to every screen aren't searched.
+    let int_query = query.trim().parse::<i64>().ok();
+    let float_query = query.trim().parse::<f64>().ok().filter(|x| x.is_finite());
+    let columns = get_columns(&tbl_name, &state).await?;
     let where_clause = columns
         .iter()
-        .map(|col| format!("{}::TEXT ILIKE $1", col.0))
+        .filter(|(col, _)| !ingest::is_reserved(col))
+        .filter_map(|(col, col_type)| match col_type {
+            ColType::TEXT => Some(format!("{col} ILIKE $1")),
+            ColType::SMALLINT | ColType::INT | ColType::BIGINT => {
+                int_query.map(|_| format!("{col} = $2"))
+            }
+            ColType::REAL | ColType::DOUBLE => float_query.map(|_| format!("{col} = $3")),
+        })
         .collect::<Vec<_>>()
         .join(" OR ");
+    if where_clause.is_empty() {
+        return Ok(vec![]);
+    }
 
-    let sql = format!(
-        "SELECT id FROM {} WHERE {}",
-        tbl_name,
-        if where_clause.is_empty() {
-            "TRUE".into()
-        } else {
-            where_clause
-        }
+    let sql = format!("SELECT id FROM {tbl_name} WHERE {where_clause}");
+    let pattern = format!(
+        "%{}%",
+        query
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
     );
-
-    let pattern = format!("%{}%", query);
     let row_ids: Vec<i32> = sqlx::query_scalar(&sql)
         .bind(pattern)
+        .bind(int_query)
+        .bind(float_query)
         .fetch_all(&*state.pool)
-        .await
-        .map_err(ServerFnError::new)?;
+        .await?;
 
     if row_ids.is_empty() {
         return Ok(vec![]);
@@ -153,36 +165,73 @@ pub async fn search_table(
 pub async fn get_rows(
     rows: Vec<usize>,
     tbl_name: String,
-) -> ServerFnResult<Vec<(usize, BTreeMap<String, DataCell>)>> {
+) -> ServerFnResult<Vec<(usize, BTreeMap<String, DataCell>, ProteinContext)>> {
+    let state = AppState::from_cx()?;
+    require_screen(&state, &tbl_name, Role::Viewer).await?;
+    let columns = get_columns(&tbl_name, &state).await?;
+    fetch_rows(&state, &tbl_name, &columns, &rows).await
+}
+
+/// The rows with the given ids in the order of the screen's file, with the protein context of
+/// their cysteine, from a screen the caller has checked exists and read the `columns` of.
+#[cfg(feature = "ssr")]
+pub(crate) async fn fetch_rows(
+    state: &AppState,
+    tbl_name: &str,
+    columns: &[(String, ColType)],
+    rows: &[usize],
+) -> ServerFnResult<Vec<(usize, BTreeMap<String, DataCell>, ProteinContext)>> {
     if rows.is_empty() {
         return Ok(vec![]);
     }
 
-    let state = AppState::from_cx()?;
-    let screen_keys = get_screen_keys_inner(&tbl_name, &state).await;
     let params = (1..=rows.len())
         .map(|i| format!("${i}"))
         .collect::<Vec<_>>()
         .join(",");
 
-    let query_str = format!(
-        "SELECT * FROM {} WHERE id IN ({}) ORDER BY id",
-        tbl_name, params
-    );
+    // Screens imported before cysteine identifiers were parsed have no cys_* columns.
+    let has_sites = columns.iter().any(|(col, _)| col == "cys_residue");
+    let order_by = file_order(columns);
+    let query_str = if has_sites {
+        format!(
+            r#"
+            SELECT t.*, p.protein_name AS __protein_name, p.gene_name AS __gene_name,
+                substring(
+                    p.sequence
+                    FROM GREATEST(t.cys_residue - {SEQUENCE_WINDOW}, 1)
+                    FOR LEAST(t.cys_residue - 1, {SEQUENCE_WINDOW}) + {SEQUENCE_WINDOW} + 1
+                ) AS __window
+            FROM {tbl_name} t
+            LEFT JOIN LATERAL (
+                SELECT * FROM screenmap.proteins p
+                WHERE p.accession = t.cys_accession
+                    OR (t.cys_accession IS NULL AND p.gene_name = t.cys_gene)
+                -- the same entry every time for genes with several
+                ORDER BY p.accession
+                LIMIT 1
+            ) p ON TRUE
+            WHERE t.id IN ({params}) ORDER BY {}
+            "#,
+            order_by.iter().map(|col| format!("t.{col}")).collect::<Vec<_>>().join(", ")
+        )
+    } else {
+        format!("SELECT * FROM {tbl_name} WHERE id IN ({params}) ORDER BY {}", order_by.join(", "))
+    };
     let mut query = sqlx::query(&query_str);
     for id in rows.iter() {
         query = query.bind(*id as i32);
     }
 
     let fetched_rows = query.fetch_all(&*state.pool).await?;
-    let result = rows
+    let result = fetched_rows
         .into_iter()
-        .zip(fetched_rows.into_iter())
-        .map(|(row_id, row)| {
-            screen_keys
-                .clone()?
-                .into_iter()
-                .map(|(col, col_type, _)| -> ServerFnResult<_> { match col_type {
+        .map(|row| {
+            let row_id = row.try_get::<i32, _>("id")? as usize;
+            columns
+                .iter()
+                .cloned()
+                .map(|(col, col_type)| -> ServerFnResult<_> { match col_type {
                     ColType::TEXT => {
                         let data = get_col::<String>(&row, col.as_str())?.map(DataCell::Text).unwrap_or_default();
                         Ok((col, data))
@@ -209,49 +258,114 @@ pub async fn get_rows(
                     }
                 }})
                 .try_collect()
-                .map(|row| (row_id, row))
+                .and_then(|cells| {
+                    let context = if has_sites { protein_context(&row)? } else { ProteinContext::default() };
+                    Ok((row_id, cells, context))
+                })
         })
         .map(|x| x)
         .try_collect()?;
     Ok(result)
 }
 
+#[cfg(feature = "ssr")]
+fn protein_context(row: &PgRow) -> ServerFnResult<ProteinContext> {
+    let residue = get_col::<i32>(row, "cys_residue")?;
+    let window = get_col::<String>(row, "__window")?.zip(residue).map(|(window, residue)| {
+        let pad_left = SEQUENCE_WINDOW.saturating_sub(residue.max(1) as usize - 1);
+        let mut window = format!("{}{}", "-".repeat(pad_left), window);
+        let width = 2 * SEQUENCE_WINDOW + 1;
+        if window.len() < width {
+            window.push_str(&"-".repeat(width - window.len()));
+        }
+        window
+    });
+    Ok(ProteinContext {
+        protein_name: get_col(row, "__protein_name")?,
+        gene_name: get_col(row, "__gene_name")?,
+        window,
+    })
+}
+
 #[cfg(feature = "ssr")]
 fn get_col<'b, 'a: 'b, T>(row: &'a PgRow, col: &str) -> ServerFnResult<Option<T>>
 where
     T: ToString + sqlx::Type<sqlx::Postgres> + sqlx::Decode<'b, sqlx::Postgres> + Debug + Default,
 {
-    row.try_get(col).map_err(ServerFnError::new)
+    row.try_get(col).map_err(AppError::new)
 }
 
 #[cfg(feature = "ssr")]
-async fn get_tbls(state: &AppState) -> ServerFnResult<Vec<String>> {
+pub(crate) async fn get_tbls(state: &AppState) -> ServerFnResult<Vec<String>> {
     sqlx::query_scalar(
         r#"
         SELECT table_name
         FROM information_schema.tables
-        WHERE table_schema NOT IN ('pg_catalog', 'information_schema')
+        WHERE table_schema = current_schema()
           AND table_type = 'BASE TABLE'
         ORDER BY table_name
         "#,
     )
     .fetch_all(state.pool.as_ref())
     .await
-    .map_err(ServerFnError::new)
+    .map_err(AppError::new)
+}
+
+/// What the admin page set for the screen, empty for screens it hasn't touched.
+#[server(name = GetScreenMeta, prefix = "/api")]
+pub async fn get_screen_meta(screen_name: String) -> ServerFnResult<ScreenMeta> {
+    let state = AppState::from_cx()?;
+    require_screen(&state, &screen_name, Role::Viewer).await?;
+    let (title, description) = sqlx::query_as(
+        "SELECT title, description FROM screenmap.screens WHERE name = $1",
+    )
+    .bind(&screen_name)
+    .fetch_optional(state.pool.as_ref())
+    .await?
+    .unwrap_or_default();
+    let display_names: Vec<(String, String)> = sqlx::query_as(
+        "SELECT column_name, display_name FROM screenmap.column_display_names WHERE screen = $1",
+    )
+    .bind(&screen_name)
+    .fetch_all(state.pool.as_ref())
+    .await?;
+    Ok(ScreenMeta {
+        title,
+        description,
+        display_names: display_names.into_iter().collect(),
+    })
 }
 
 #[server(name = ScreenKeys, prefix = "/api")]
 pub async fn get_screen_keys(screen_name: String) -> ServerFnResult<Vec<(String, ColType, Option<(f64, f64)>)>> {
     let state = AppState::from_cx()?;
+    require_screen(&state, &screen_name, Role::Viewer).await?;
     get_screen_keys_inner(&screen_name, &state).await
 }
 
+/// The column process_csv recorded as identifying rows, or the first column for screens
+/// imported before it was recorded.
 #[cfg(feature = "ssr")]
-async fn get_screen_keys_inner(
-    screen_name: &str,
-    state: &AppState,
-) -> ServerFnResult<Vec<(String, ColType, Option<(f64, f64)>)>> {
-    let columns = sqlx::query(
+pub(crate) async fn key_column(screen_name: &str, state: &AppState) -> Result<String, sqlx::Error> {
+    sqlx::query_scalar::<_, Option<String>>(
+        r#"
+        SELECT COALESCE(
+            (SELECT key_column FROM screenmap.screens WHERE name = $1),
+            (SELECT column_name::TEXT FROM information_schema.columns
+             WHERE table_name = $1 AND table_schema = current_schema()
+             ORDER BY ordinal_position LIMIT 1)
+        )
+        "#,
+    )
+    .bind(screen_name)
+    .fetch_one(state.pool.as_ref())
+    .await?
+    .ok_or(sqlx::Error::RowNotFound)
+}
+
+#[cfg(feature = "ssr")]
+pub(crate) async fn get_columns(screen_name: &str, state: &AppState) -> ServerFnResult<Vec<(String, ColType)>> {
+    Ok(sqlx::query(
         r#"
         SELECT column_name, data_type
         FROM information_schema.columns
@@ -268,7 +382,26 @@ async fn get_screen_keys_inner(
         ))
     })
     .fetch_all(&*state.pool)
-    .await?;
+    .await?)
+}
+
+/// The columns that put a screen's rows in the order of the file they were imported from.
+/// Screens imported before source_line was recorded fall back to id.
+#[cfg(feature = "ssr")]
+pub(crate) fn file_order(columns: &[(String, ColType)]) -> Vec<&'static str> {
+    if columns.iter().any(|(col, _)| col == "source_line") {
+        vec!["source_line", "id"]
+    } else {
+        vec!["id"]
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub(crate) async fn get_screen_keys_inner(
+    screen_name: &str,
+    state: &AppState,
+) -> ServerFnResult<Vec<(String, ColType, Option<(f64, f64)>)>> {
+    let columns = get_columns(screen_name, state).await?;
 
     let double_columns: Vec<&str> = columns
         .iter()
@@ -283,9 +416,12 @@ async fn get_screen_keys_inner(
             .map(|col| {
                 // Sanitize column name for alias generation
                 let alias_safe = col.replace('\"', "_");
+                let col = col.replace('\"', "\"\"");
+                // NaN and +-Infinity would swamp the heatmap scale, so they're left out of it.
+                let finite = format!("\"{col}\" NOT IN ('NaN', 'Infinity', '-Infinity')");
                 format!(
-                    "min(\"{col}\") as \"min_{alias_safe}\", max(\"{col}\") as \"max_{alias_safe}\"",
-                    col = col.replace('\"', "\"\"")
+                    "min(\"{col}\") FILTER (WHERE {finite}) as \"min_{alias_safe}\", \
+                     max(\"{col}\") FILTER (WHERE {finite}) as \"max_{alias_safe}\""
                 )
             })
             .collect();
@@ -304,10 +440,13 @@ async fn get_screen_keys_inner(
                 for col in &double_columns {
                     // Use the same sanitized alias when retrieving
                     let alias_safe = col.replace('\"', "_");
-                    let min: f64 = row.try_get(format!("min_{}", alias_safe).as_str())?;
-                    let max: f64 = row.try_get(format!("max_{}", alias_safe).as_str())?;
+                    let min: Option<f64> = row.try_get(format!("min_{}", alias_safe).as_str())?;
+                    let max: Option<f64> = row.try_get(format!("max_{}", alias_safe).as_str())?;
 
-                    min_max_map.insert(*col, (min, max));
+                    // Both are NULL when the column has no finite values.
+                    if let Some(min_max) = min.zip(max) {
+                        min_max_map.insert(*col, min_max);
+                    }
                 }
             }
             Err(e) => {
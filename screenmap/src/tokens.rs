@@ -0,0 +1,137 @@
+//! Personal API tokens, for scripts and notebooks that read screens without a browser. They are
+//! sent as `Authorization: Bearer <token>` to the server functions and the export, and allow what
+//! their user can do within their scopes.
+use crate::{
+    error::AppError,
+    interface::{ApiToken, Scope},
+};
+use leptos::server;
+use server_fn::codec::Json;
+
+type ServerFnResult<T> = Result<T, AppError>;
+
+cfg_if::cfg_if! {
+if #[cfg(feature = "ssr")] {
+    use crate::auth::{new_token, require_session, token_hash};
+    use crate::interface::User;
+    use crate::server::AppState;
+    use sqlx::{PgPool, Row};
+
+    /// Tokens start with this, so they can be told apart from other secrets, e.g. by scanners.
+    const TOKEN_PREFIX: &str = "smt_";
+
+    /// The user of the token and its scopes, unless it doesn't exist or has expired.
+    pub async fn token_user(pool: &PgPool, token: &str) -> Result<Option<(User, Vec<Scope>)>, sqlx::Error> {
+        let Some(row) = sqlx::query(
+            r#"
+            UPDATE screenmap.api_tokens t SET last_used_at = now()
+            FROM screenmap.users u
+            WHERE u.id = t.user_id AND t.token_hash = $1
+                AND (t.expires_at IS NULL OR t.expires_at > now())
+            RETURNING u.id, u.username, u.is_admin, t.scopes
+            "#,
+        )
+        .bind(token_hash(token))
+        .fetch_optional(pool)
+        .await?
+        else {
+            return Ok(None);
+        };
+        let scopes: Vec<String> = row.try_get("scopes")?;
+        let user = User {
+            id: row.try_get("id")?,
+            username: row.try_get("username")?,
+            is_admin: row.try_get("is_admin")?,
+        };
+        Ok(Some((user, scopes.iter().filter_map(|scope| Scope::parse(scope)).collect())))
+    }
+}}
+
+/// Makes a token for the logged in user and returns it, which is the only time it can be seen.
+/// It expires after `expires_in_days`, or never with None.
+#[server(name = CreateToken, prefix = "/api", input = Json)]
+pub async fn create_token(
+    name: String,
+    scopes: Vec<Scope>,
+    expires_in_days: Option<i32>,
+) -> ServerFnResult<String> {
+    let user = require_session()?;
+    let state = AppState::from_cx()?;
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::new("Give the token a name."));
+    } else if scopes.is_empty() {
+        return Err(AppError::new("Give the token at least one scope."));
+    } else if expires_in_days.is_some_and(|days| days <= 0) {
+        return Err(AppError::new("Tokens must last at least a day."));
+    }
+    let token = format!("{TOKEN_PREFIX}{}", new_token());
+    let scopes: Vec<_> = scopes.iter().map(Scope::as_str).collect();
+    sqlx::query(
+        r#"
+        INSERT INTO screenmap.api_tokens (user_id, name, token_hash, scopes, expires_at)
+        VALUES ($1, $2, $3, $4, now() + make_interval(days => $5))
+        "#,
+    )
+    .bind(user.id)
+    .bind(name)
+    .bind(token_hash(&token))
+    .bind(scopes)
+    .bind(expires_in_days)
+    .execute(state.pool.as_ref())
+    .await?;
+    Ok(token)
+}
+
+/// The tokens of the logged in user, expired ones included.
+#[server(name = ListTokens, prefix = "/api")]
+pub async fn list_tokens() -> ServerFnResult<Vec<ApiToken>> {
+    let user = require_session()?;
+    let state = AppState::from_cx()?;
+    let rows = sqlx::query(
+        r#"
+        SELECT id, name, scopes,
+            to_char(created_at, 'YYYY-MM-DD HH24:MI') AS created_at,
+            to_char(expires_at, 'YYYY-MM-DD HH24:MI') AS expires_at,
+            to_char(last_used_at, 'YYYY-MM-DD HH24:MI') AS last_used_at
+        FROM screenmap.api_tokens
+        WHERE user_id = $1
+        ORDER BY id
+        "#,
+    )
+    .bind(user.id)
+    .fetch_all(state.pool.as_ref())
+    .await?;
+    let mut tokens = vec![];
+    for row in rows {
+        let scopes: Vec<String> = row.try_get("scopes")?;
+        tokens.push(ApiToken {
+            id: row.try_get("id")?,
+            name: row.try_get("name")?,
+            scopes: scopes
+                .iter()
+                .filter_map(|scope| Scope::parse(scope))
+                .collect(),
+            created_at: row.try_get("created_at")?,
+            expires_at: row.try_get("expires_at")?,
+            last_used_at: row.try_get("last_used_at")?,
+        });
+    }
+    Ok(tokens)
+}
+
+/// Deletes one of the logged in user's tokens, which stops working at once.
+#[server(name = RevokeToken, prefix = "/api")]
+pub async fn revoke_token(id: i64) -> ServerFnResult<()> {
+    let user = require_session()?;
+    let state = AppState::from_cx()?;
+    let revoked = sqlx::query("DELETE FROM screenmap.api_tokens WHERE id = $1 AND user_id = $2")
+        .bind(id)
+        .bind(user.id)
+        .execute(state.pool.as_ref())
+        .await?;
+    if revoked.rows_affected() == 0 {
+        return Err(AppError::new("There is no such token."));
+    }
+    Ok(())
+}
@@ -0,0 +1,263 @@
+//! Server side of the admin upload page, which loads a file with the same inference as
+//! process_csv and lets the admin correct it before importing.
+use crate::{
+    error::AppError,
+    interface::{Job, UploadColumn, UploadPreview},
+};
+use leptos::server;
+use server_fn::codec::{Json, MultipartData, MultipartFormData};
+
+type ServerFnResult<T> = Result<T, AppError>;
+
+cfg_if::cfg_if! {
+if #[cfg(feature = "ssr")] {
+    use crate::interface::ColType;
+    use crate::audit;
+    use crate::auth::require_admin;
+    use crate::jobs::ImportRequest;
+    use crate::projects::project_id;
+    use crate::server::AppState;
+    use ingest::{
+        CSVProcessor, RecordType, catalog,
+        cys_id::CysIdParser,
+        formats::{Format, FormatReader},
+        input::Input,
+        uniprot,
+        values::{NonFinite, ValueParser},
+    };
+    use log::error;
+    use sqlx::PgPool;
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+
+    /// Rows of the file shown under the columns.
+    const SAMPLE_ROWS: usize = 5;
+
+    /// How long an upload that was never imported is kept.
+    const UPLOAD_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+    pub(crate) fn uploads_dir() -> PathBuf {
+        std::env::temp_dir().join("screenmap-uploads")
+    }
+
+    /// The stored file of an upload, which is kept in a directory of its own named by its id.
+    fn upload_path(upload_id: &str) -> ServerFnResult<PathBuf> {
+        let not_found = || AppError::new("Upload not found, upload the file again.");
+        if upload_id.is_empty() || !upload_id.chars().all(|c| c.is_ascii_hexdigit() || c == '-') {
+            return Err(not_found());
+        }
+        std::fs::read_dir(uploads_dir().join(upload_id))
+            .map_err(|_| not_found())?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .next()
+            .ok_or_else(not_found)
+    }
+
+    /// Deletes an upload, once its job is done with it or it was abandoned.
+    pub(crate) fn remove_upload(upload_id: &str) {
+        let dir = uploads_dir().join(upload_id);
+        match std::fs::remove_dir_all(&dir) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                error!("Failed to remove the upload {dir:?}: {e}")
+            }
+            _ => {}
+        }
+    }
+
+    /// Deletes the uploads older than UPLOAD_TTL that no queued or running job is importing.
+    pub(crate) async fn expire_uploads(pool: &PgPool) -> Result<(), sqlx::Error> {
+        let Ok(entries) = std::fs::read_dir(uploads_dir()) else {
+            return Ok(());
+        };
+        let active: Vec<String> = sqlx::query_scalar(
+            "SELECT upload_id FROM screenmap.jobs WHERE state IN ('queued', 'running')",
+        )
+        .fetch_all(pool)
+        .await?;
+        for entry in entries.filter_map(Result::ok) {
+            let expired = entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| {
+                    SystemTime::now()
+                        .duration_since(modified)
+                        .is_ok_and(|age| age > UPLOAD_TTL)
+                });
+            let upload_id = entry.file_name().to_string_lossy().to_string();
+            if expired && !active.contains(&upload_id) {
+                remove_upload(&upload_id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Previews how the stored upload at `path` would be imported.
+    async fn preview_upload(
+        state: &AppState,
+        upload_id: String,
+        path: PathBuf,
+    ) -> ServerFnResult<UploadPreview> {
+        let file_name = path
+            .file_name()
+            .map_or(String::new(), |name| name.to_string_lossy().to_string());
+        let (processor, _) = open_upload(&state.pool, path, None)
+            .await
+            .map_err(AppError::new)?;
+        let exists = catalog::recorded_import(state.pool.as_ref(), &processor.table_name)
+            .await
+            .map_err(AppError::new)?
+            .is_some();
+        let columns = processor
+            .headers
+            .iter()
+            .zip(processor.columns.iter())
+            .zip(processor.header_types.iter())
+            .map(|((header, name), record_type)| UploadColumn {
+                header: header.clone(),
+                name: name.clone(),
+                col_type: col_type(*record_type),
+            })
+            .collect();
+        let sample = tokio::task::block_in_place(|| processor.sample_rows(SAMPLE_ROWS))
+            .map_err(AppError::new)?;
+        Ok(UploadPreview {
+            upload_id,
+            file_name,
+            table: processor.table_name,
+            exists,
+            columns,
+            sample,
+        })
+    }
+
+    pub(crate) fn record_type(col_type: ColType) -> RecordType {
+        match col_type {
+            ColType::SMALLINT | ColType::INT | ColType::BIGINT => RecordType::BIGINT,
+            ColType::REAL | ColType::DOUBLE => RecordType::DOUBLE,
+            ColType::TEXT => RecordType::TEXT,
+        }
+    }
+
+    fn col_type(record_type: RecordType) -> ColType {
+        match record_type {
+            RecordType::BIGINT => ColType::BIGINT,
+            RecordType::DOUBLE => ColType::DOUBLE,
+            RecordType::TEXT => ColType::TEXT,
+        }
+    }
+
+    /// Infers the upload's columns the way process_csv does with no options, into `table` or
+    /// the table named after the file. Also returns the file's preamble.
+    pub(crate) async fn open_upload(
+        pool: &PgPool,
+        path: PathBuf,
+        table: Option<&str>,
+    ) -> anyhow::Result<(CSVProcessor, Vec<String>)> {
+        let format = Format::detect(&path);
+        // Search engines write tab separated tables.
+        let delimiter = (format != Format::Csv).then_some(b'\t');
+        let sequences = if format.needs_sequences() {
+            uniprot::sequences(pool).await?
+        } else {
+            Default::default()
+        };
+        // Type inference reads the whole file.
+        tokio::task::block_in_place(|| {
+            let input = Input::open(path, None, delimiter, 0, 1)?;
+            let table_name = ingest::table_name(&input, table)?;
+            let preamble = input.preamble()?;
+            let value_parser = ValueParser::new(&[], NonFinite::Null, input.sniff_decimal()?);
+            let processor = CSVProcessor::new(
+                input,
+                table_name,
+                None,
+                FormatReader::new(format, sequences),
+                &CysIdParser::new(&[])?,
+                value_parser,
+                None,
+            )?;
+            Ok((processor, preamble))
+        })
+    }
+}}
+
+/// Stores the file in the `file` field of the form and previews how it would be imported.
+#[server(name = UploadScreen, prefix = "/api", input = MultipartFormData)]
+pub async fn upload_screen(data: MultipartData) -> ServerFnResult<UploadPreview> {
+    require_admin()?;
+    let state = AppState::from_cx()?;
+    let mut data = data.into_inner().ok_or(AppError::new("Expected a form."))?;
+    let mut file = None;
+    while let Some(field) = data.next_field().await.map_err(AppError::new)? {
+        if field.name() == Some("file") {
+            // Only the name is kept, whatever path the browser sends.
+            let file_name = field
+                .file_name()
+                .and_then(|name| PathBuf::from(name).file_name().map(|name| name.to_owned()))
+                .ok_or(AppError::new("The file has no name."))?;
+            let bytes = field.bytes().await.map_err(AppError::new)?;
+            file = Some((file_name, bytes));
+        }
+    }
+    let Some((file_name, bytes)) = file.filter(|(_, bytes)| !bytes.is_empty()) else {
+        return Err(AppError::new("Choose a file to upload."));
+    };
+
+    expire_uploads(&state.pool).await?;
+    // Every upload gets a directory of its own, even if the same file was uploaded before.
+    std::fs::create_dir_all(uploads_dir())?;
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_err(AppError::new)?
+        .as_nanos();
+    let upload_id = format!("{:x}-{nanos:x}", std::process::id());
+    let upload_dir = uploads_dir().join(&upload_id);
+    std::fs::create_dir(&upload_dir)?;
+    let path = upload_dir.join(&file_name);
+    let preview = match std::fs::write(&path, &bytes) {
+        Ok(()) => preview_upload(&state, upload_id.clone(), path).await,
+        Err(e) => Err(e.into()),
+    };
+    if preview.is_err() {
+        remove_upload(&upload_id);
+    }
+    preview
+}
+
+/// Queues the import of an upload into `table` with the columns as the admin edited them, which
+/// the page follows over /ws/jobs/{id}. An existing table is only replaced with `replace`. The
+/// screen is put in `project`, or else a replaced one stays where it is and a new one is
+/// published.
+#[server(name = ImportUpload, prefix = "/api", input = Json)]
+pub async fn import_upload(
+    upload_id: String,
+    table: String,
+    columns: Vec<UploadColumn>,
+    replace: bool,
+    project: Option<String>,
+) -> ServerFnResult<Job> {
+    require_admin()?;
+    let state = AppState::from_cx()?;
+    let queued_by = audit::actor()?;
+    if let Some(project) = &project {
+        project_id(&state, project).await?;
+    }
+    let path = upload_path(&upload_id)?;
+    let file_name = path
+        .file_name()
+        .map_or(String::new(), |name| name.to_string_lossy().to_string());
+    let request = ImportRequest {
+        upload_id: &upload_id,
+        file_name: &file_name,
+        table_name: &table,
+        columns: &columns,
+        replace,
+        project: project.as_deref(),
+    };
+    state
+        .jobs
+        .enqueue(&request, &queued_by)
+        .await
+        .map_err(AppError::new)
+}